@@ -0,0 +1,319 @@
+//! Cyclic transmission by the kernel through the broadcast manager (CAN_BCM)
+//!
+//! A transmission job is set up once with [`BcmSocket::tx_setup`], afterwards
+//! the kernel sends the frame with precise timing independent of the process.
+//! Jobs are identified by the CAN ID of their frame and removed with
+//! [`BcmSocket::tx_delete`] or when the socket is closed.
+
+use crate::fd::{check_write, CanFdFrame};
+use crate::frame::AnyFrame;
+use crate::id::CanId;
+use std::ffi::CString;
+use std::io;
+use std::mem::size_of;
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::time::Duration;
+
+/// Protocol of broadcast manager sockets
+const CAN_BCM: libc::c_int = 2;
+
+/// Create (or update) a transmission job
+const TX_SETUP: u32 = 1;
+/// Remove a transmission job
+const TX_DELETE: u32 = 2;
+/// Notification that a job sent all frames of its count
+const TX_EXPIRED: u32 = 9;
+
+/// Set the intervals and the count of the job
+const SETTIMER: u32 = 0x0001;
+/// Start the timer of the job
+const STARTTIMER: u32 = 0x0002;
+/// Send `TX_EXPIRED` when the count ran out
+const TX_COUNTEVT: u32 = 0x0004;
+/// Send the frame immediately once, implied by `STARTTIMER`
+const TX_ANNOUNCE: u32 = 0x0008;
+/// The job sends CAN FD frames
+const CAN_FD_FRAME: u32 = 0x0800;
+
+/// Same memory layout as the kernel struct `bcm_timeval`
+#[derive(Debug, Default, Copy, Clone)]
+#[repr(C)]
+struct BcmTimeval {
+    tv_sec: libc::c_long,
+    tv_usec: libc::c_long,
+}
+
+impl From<Duration> for BcmTimeval {
+    fn from(duration: Duration) -> BcmTimeval {
+        BcmTimeval {
+            tv_sec: duration.as_secs() as libc::c_long,
+            tv_usec: duration.subsec_micros() as libc::c_long,
+        }
+    }
+}
+
+/// Same memory layout as the kernel struct `bcm_msg_head`
+#[derive(Debug, Default, Copy, Clone)]
+#[repr(C)]
+struct BcmMsgHead {
+    opcode: u32,
+    flags: u32,
+    count: u32,
+    ival1: BcmTimeval,
+    ival2: BcmTimeval,
+    can_id: u32,
+    nframes: u32,
+}
+
+/// Frame following the message head, the kernel aligns frames to 8 bytes
+#[derive(Debug, Copy, Clone)]
+#[repr(C, align(8))]
+struct Aligned<F>(F);
+
+/// Message head with a single frame
+#[derive(Debug, Copy, Clone)]
+#[repr(C)]
+struct BcmMsg<F> {
+    head: BcmMsgHead,
+    frame: Aligned<F>,
+}
+
+/// Same memory layout as the kernel struct `sockaddr_can` (without the protocol specific addresses)
+#[repr(C)]
+struct SockaddrCan {
+    can_family: libc::sa_family_t,
+    can_ifindex: libc::c_int,
+    rx_id: u32,
+    tx_id: u32,
+}
+
+/// Creates the head of a message for the job of the given frame
+/// # Arguments
+/// * 'opcode' - operation of the message
+/// * 'flags' - flags of the message, `CAN_FD_FRAME` is added for CAN FD frames
+/// * 'frame' - frame identifying the job
+fn message_head(opcode: u32, flags: u32, frame: &AnyFrame) -> BcmMsgHead {
+    BcmMsgHead {
+        opcode,
+        flags: if frame.is_fd() { flags | CAN_FD_FRAME } else { flags },
+        can_id: frame.can_id().raw(),
+        ..BcmMsgHead::default()
+    }
+}
+
+/// Check if a job with the given count reports `TX_EXPIRED` after its last frame
+///
+/// Starting the timer sends the first frame and decrements the count, so a
+/// job with a count of 1 never starts its timer (and never expires).
+/// # Arguments
+/// * 'count' - count of frames of the job, `0` sends until the job is deleted
+fn reports_expiry(count: u32) -> bool {
+    count > 1
+}
+
+/// Creates the head of the `TX_SETUP` message of a new job
+/// # Arguments
+/// * 'frame' - frame to send
+/// * 'interval' - time between two frames
+/// * 'count' - count of frames to send, `0` sends until the job is deleted
+fn setup_head(frame: &AnyFrame, interval: Duration, count: u32) -> BcmMsgHead {
+    match count {
+        0 => BcmMsgHead {
+            ival2: interval.into(),
+            ..message_head(TX_SETUP, SETTIMER | STARTTIMER, frame)
+        },
+        // a single frame needs no timer
+        1 => message_head(TX_SETUP, TX_ANNOUNCE, frame),
+        _ => BcmMsgHead {
+            count,
+            ival1: interval.into(),
+            // TX_EXPIRED tells when the count ran out
+            ..message_head(TX_SETUP, SETTIMER | STARTTIMER | TX_COUNTEVT, frame)
+        },
+    }
+}
+
+/// Socket of the broadcast manager connected to a CAN interface
+#[derive(Debug)]
+pub struct BcmSocket {
+    fd: RawFd,
+}
+
+impl BcmSocket {
+    /// Opens a broadcast manager socket on an interface
+    /// # Arguments
+    /// * 'ifname' - name of the CAN interface, e.g. `can0`
+    pub fn open(ifname: &str) -> io::Result<BcmSocket> {
+        let c_ifname: CString = CString::new(ifname)
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "invalid interface name"))?;
+        let if_index = unsafe { libc::if_nametoindex(c_ifname.as_ptr()) };
+        if if_index == 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let fd: RawFd = unsafe { libc::socket(libc::PF_CAN, libc::SOCK_DGRAM, CAN_BCM) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        // closes the file descriptor if connecting fails
+        let socket: BcmSocket = BcmSocket { fd };
+        let addr: SockaddrCan = SockaddrCan {
+            can_family: libc::AF_CAN as libc::sa_family_t,
+            can_ifindex: if_index as libc::c_int,
+            rx_id: 0,
+            tx_id: 0,
+        };
+        let connect_rv = unsafe {
+            libc::connect(fd,
+                          &addr as *const SockaddrCan as *const libc::sockaddr,
+                          size_of::<SockaddrCan>() as libc::socklen_t)
+        };
+        if connect_rv < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(socket)
+    }
+
+    /// Writes a message to the socket
+    fn write_message<M>(&self, message: &M) -> io::Result<()> {
+        let write_rv = unsafe {
+            libc::write(self.fd,
+                        message as *const M as *const libc::c_void,
+                        size_of::<M>())
+        };
+        check_write(write_rv, size_of::<M>())
+    }
+
+    /// Writes a message with a single frame to the socket
+    fn write_frame_message(&self, head: BcmMsgHead, frame: &AnyFrame) -> io::Result<()> {
+        let head: BcmMsgHead = BcmMsgHead { nframes: 1, ..head };
+        match frame {
+            AnyFrame::Classic(frame) => self.write_message(&BcmMsg { head, frame: Aligned(*frame) }),
+            AnyFrame::Fd(frame) => self.write_message::<BcmMsg<CanFdFrame>>(&BcmMsg { head, frame: Aligned(*frame) }),
+        }
+    }
+
+    /// Creates a job sending the frame cyclically
+    ///
+    /// The first frame is sent immediately, the following ones after every interval.
+    ///
+    /// Returns whether the job reports with [`BcmSocket::read_expired`] when it
+    /// sent all frames of the count, which is the case for counts above 1.
+    /// # Arguments
+    /// * 'frame' - frame to send, CAN FD frames are supported
+    /// * 'interval' - time between two frames
+    /// * 'count' - count of frames to send, `0` sends until the job is deleted
+    pub fn tx_setup(&self, frame: &AnyFrame, interval: Duration, count: u32) -> io::Result<bool> {
+        self.write_frame_message(setup_head(frame, interval, count), frame)?;
+        Ok(reports_expiry(count))
+    }
+
+    /// Updates the data of the job with the ID of the frame, keeping its timing
+    /// # Arguments
+    /// * 'frame' - frame with the new data
+    /// * 'count' - count the job was set up with, keeps its expiry notification
+    pub fn tx_update(&self, frame: &AnyFrame, count: u32) -> io::Result<()> {
+        // the kernel replaces the flags of the job with the ones of the update
+        let flags: u32 = if reports_expiry(count) { TX_COUNTEVT } else { 0 };
+        self.write_frame_message(message_head(TX_SETUP, flags, frame), frame)
+    }
+
+    /// Deletes the job with the ID of the frame
+    /// # Arguments
+    /// * 'frame' - frame identifying the job
+    pub fn tx_delete(&self, frame: &AnyFrame) -> io::Result<()> {
+        self.write_message(&message_head(TX_DELETE, 0, frame))
+    }
+
+    /// Blocking read of a notification, returns the ID of a job which sent all frames of its count
+    ///
+    /// Returns `None` for other notifications.
+    pub fn read_expired(&self) -> io::Result<Option<CanId>> {
+        let mut head: BcmMsgHead = BcmMsgHead::default();
+        // frames following the head are discarded
+        let read_rv = unsafe {
+            libc::read(self.fd,
+                       &mut head as *mut BcmMsgHead as *mut libc::c_void,
+                       size_of::<BcmMsgHead>())
+        };
+        if read_rv < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        if (read_rv as usize) < size_of::<BcmMsgHead>() {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "incomplete broadcast manager message"));
+        }
+        match head.opcode {
+            TX_EXPIRED => Ok(Some(CanId::from_raw(head.can_id))),
+            _ => Ok(None),
+        }
+    }
+}
+
+impl AsRawFd for BcmSocket {
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd
+    }
+}
+
+impl Drop for BcmSocket {
+    fn drop(&mut self) {
+        // the kernel removes the remaining jobs of the socket
+        unsafe {
+            libc::close(self.fd);
+        }
+    }
+}
+
+#[cfg(test)]
+use crate::frame::parse_frame_string;
+
+#[test]
+#[cfg(target_pointer_width = "64")]
+fn test_bcm_message_layout() {
+    assert_eq!(56, size_of::<BcmMsgHead>());
+    assert_eq!(56 + libc::CAN_MTU, size_of::<BcmMsg<socketcan::CANFrame>>());
+    assert_eq!(56 + libc::CANFD_MTU, size_of::<BcmMsg<CanFdFrame>>());
+}
+
+#[test]
+fn test_message_head() {
+    let frame: AnyFrame = parse_frame_string("123#cafe").unwrap();
+    let head: BcmMsgHead = message_head(TX_DELETE, 0, &frame);
+    assert_eq!(TX_DELETE, head.opcode);
+    assert_eq!(0, head.flags);
+    assert_eq!(0x123, head.can_id);
+    assert_eq!(0, head.nframes);
+
+    let frame: AnyFrame = parse_frame_string("12345678##1cafe").unwrap();
+    let head: BcmMsgHead = message_head(TX_SETUP, SETTIMER, &frame);
+    assert_eq!(SETTIMER | CAN_FD_FRAME, head.flags);
+    assert_eq!(0x92345678, head.can_id);
+
+    let timeval: BcmTimeval = Duration::from_micros(1_250_500).into();
+    assert_eq!(1, timeval.tv_sec);
+    assert_eq!(250_500, timeval.tv_usec);
+}
+
+#[test]
+fn test_setup_head() {
+    let frame: AnyFrame = parse_frame_string("123#cafe").unwrap();
+    let interval: Duration = Duration::from_millis(100);
+
+    let head: BcmMsgHead = setup_head(&frame, interval, 0);
+    assert_eq!(SETTIMER | STARTTIMER, head.flags);
+    assert_eq!(0, head.count);
+    assert_eq!(100_000, head.ival2.tv_usec);
+    assert!(!reports_expiry(0));
+
+    // single frame without timer, so no TX_EXPIRED is awaited
+    let head: BcmMsgHead = setup_head(&frame, interval, 1);
+    assert_eq!(TX_ANNOUNCE, head.flags);
+    assert_eq!(0, head.count);
+    assert!(!reports_expiry(1));
+
+    let head: BcmMsgHead = setup_head(&frame, interval, 5);
+    assert_eq!(SETTIMER | STARTTIMER | TX_COUNTEVT, head.flags);
+    assert_eq!(5, head.count);
+    assert_eq!(100_000, head.ival1.tv_usec);
+    assert_eq!(0, head.ival2.tv_usec);
+    assert!(reports_expiry(5));
+}
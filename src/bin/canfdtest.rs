@@ -1,395 +1,222 @@
-use clap::{App, Arg};
+use can_utils::canfdtest::pattern::Pattern;
+use can_utils::canfdtest::{dut, host, local, report, DEFAULT_DUT_ID, DEFAULT_HOST_ID};
+use can_utils::id::CanId;
+use can_utils::shutdown;
+use clap::{App, Arg, ArgGroup, ArgMatches};
 use log::LevelFilter;
+use std::fs;
 use std::process;
-use socketcan::CANFrame;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use simple_logger::SimpleLogger;
 
-const DEFAULT_INFLIGHT_COUNT: usize = 50;
-
-fn increment_frame(frame: CANFrame) -> Option<CANFrame> {
-    let frame_id: u32 = frame.id() + 1;
-    let mut frame_data: Vec<u8> = vec![0; frame.data().len() as usize];
-    frame_data[..].clone_from_slice(&frame.data());
-    for i in 0..frame_data.len() {
-        // handled attempt to add with overflow
-        if frame_data[i] >= 255 {
-            frame_data[i] = 0;
-        } else {
-            frame_data[i] += 1;
-        }
-    }
-    match CANFrame::new(frame_id, &frame_data, false, false) {
-        Ok(frame) => Some(frame),
-        Err(_) => None,
+/// Parses a time in µs from a program argument, exits on invalid values
+/// # Arguments
+/// * 'value' - value of the argument
+/// * 'description' - description of the argument for the error message
+fn micros(value: &str, description: &str) -> Duration {
+    match value.parse() {
+        Ok(micros) => Duration::from_micros(micros),
+        Err(_) => {
+            log::error!("No valid {} given!", description);
+            process::exit(1);
+        },
     }
 }
 
-mod host {
-    use log;
-    use socketcan::{CANFrame, CANSocket};
-    use std::fmt;
-    use std::time::{Duration};
-    use std::thread;
-
-    const CAN_MSG_ID: u32 = 0x77;
-
-    #[derive(Debug)]
-    pub struct HostError {
-        details: String
-    }
-
-    impl HostError {
-        fn new(msg: &str) -> HostError {
-            HostError{details: msg.to_string()}
-        }
-    }
-
-    pub struct Host {
-        socket: CANSocket,
-        inflight_count: usize,
-        frame_count: usize,
-    }
-
-    impl Host {
-
-        fn compare_frame(expected_frame: CANFrame, received_frame: CANFrame, increment: usize) -> Result<bool, HostError> {
-            if increment == 0 {
-                if expected_frame.id() != received_frame.id() {
-                    log::error!("Expected ID: {}, Received ID: {}", expected_frame.id(), received_frame.id());
-                    return Err(HostError::new("ID mismatch!"));
-                } else {
-                    for i in 0..expected_frame.data().len() {
-                        if expected_frame.data()[i] != received_frame.data()[i] {
-                            log::error!("Expected data: {:x?}, Received data: {:x?}", expected_frame.data(), received_frame.data());
-                            return Err(HostError::new("Data byte mismatch!"));
-                        } else {
-                            continue;
-                        }
-                    }
-                }
-                Ok(true)
-             } else {
-                 let new_expected_frame: CANFrame = match super::increment_frame(expected_frame) {
-                     Some(f) => f,
-                     None => return Err(HostError::new("Could not compare expteded receive from DUT!")),
-                 };
-                 if Host::compare_frame(new_expected_frame, received_frame, 0 as usize).is_err() {
-                     Ok(false)
-                 } else {
-                     Ok(true)
-                 }
-             }
-        }
-
-        pub fn new(socket: &str, inflight_count: usize, frame_count: usize) -> Result<Host, HostError> {
-
-            let can: CANSocket = match CANSocket::open(socket) {
-                Ok(socket) => socket,
-                Err(_) => return Err(HostError::new("Error opening socket!")),
-            };
-            //TODO: set sockopt to receive own frames
-            //can.set_recv_own_msg(); -> not supported in socketcan crate as of version 1.7.0
-
-            let host = Host {
-                socket: can,
-                inflight_count: inflight_count,
-                frame_count: frame_count,
-            };
-            Ok(host)
-        }
-
-        pub fn run(self) {
-            let mut byte_counter: u8 = 0;
-            let mut _loop_count: usize = 0;
-            let mut tx_frames: Vec<CANFrame> = Vec::with_capacity(self.inflight_count); 
-            // let mut response: Vec<bool> = Vec::with_capacity(self.inflight_count);
-
-            loop {
-                if tx_frames.len() < self.inflight_count {
-                    // line is commented out since checking for own frames is not possible ATM
-                    // response.push(true);
-                    let mut data_bytes: [u8;8] = [0; 8];
-                    for i in 0..data_bytes.len() {
-                        let counted_bytes: usize = byte_counter as usize;
-                        let byte: u8 = if counted_bytes + i > 255 {
-                            let result: usize = counted_bytes + i - 256;
-                            result as u8
-                        } else {
-                            byte_counter + i as u8
-                        };
-                        data_bytes[i] = byte;
-                    }
-                    let frame: CANFrame = match CANFrame::new(CAN_MSG_ID, &data_bytes[..], false, false) {
-                        Ok(f) => f,
-                        Err(_) => {
-                            log::error!("Could not create frame for sending! At index {}", &tx_frames.len());
-                            break;
-                        },
-                    };
-                    match self.socket.write_frame_insist(&frame) {
-                        Ok(_) => {
-                            tx_frames.push(frame);
-                        },
-                        Err(_) => {
-                            log::error!("Could not send frame! Frame: {:x?} at index {}", &frame, &tx_frames.len());
-                            break;
-                        },
-                    }
-                    if byte_counter == 255 {
-                        byte_counter = 0;
-                    } else {
-                        byte_counter += 1;
-                    }
-                    if byte_counter % 33 == 0 {
-                        thread::sleep(Duration::from_millis(3));
-                    } else {
-                        thread::sleep(Duration::from_millis(1));
-                    }
-                } else {
-                    let received_frame: CANFrame = match self.socket.read_frame() {
-                        Ok(frame) => {
-                            log::debug!{"Received frame: {:x?}", &frame};
-                            frame
-                        },
-                        Err(e) => {
-                            log::error!("Error receiving frame: {}", e);
-                            break;
-                        },
-                    };
-
-                    //TODO: receiving own frame is possible with version 2.0.0 of crate socketcan (but does not build)
-                    // if received_frame.id() == CAN_MSG_ID {
-                    //     log::debug!("Received own frame.");
-                    //     response[index] = match Host::compare_frame(tx_frames[index], received_frame, 0) {
-                    //         Ok(result) => {
-                    //             response.push(result)
-                    //         },
-                    //         Err(_) => false,
-                    //     };
-                    // } else {
-                        // if response.remove(0) {
-                            log::debug!("Received DUT frame.");
-                            let expected_frame: CANFrame = tx_frames.remove(0);
-                            match Host::compare_frame(expected_frame, received_frame, 1) {
-                                Ok(result) => {
-                                    if result {
-                                        // loop_count += 1;
-                                        log::debug!("Frame comparison passed.");
-                                        continue;
-                                    } else {
-                                        log::error!("Frame comparison failed!");
-                                        break;
-                                    }
-                                },
-                                Err(_) => break,
-                            }
-                    //     } else {
-                    //         log::error!("Did not receive own frame! Rx before Tx!");
-                    //         break;
-                    //     }
-                    // }
-                }
-            }
-        }
+/// Reads the data pattern from the program arguments
+/// # Arguments
+/// * 'arg_matches' - parsed program arguments
+fn pattern(arg_matches: &ArgMatches) -> Pattern {
+    match arg_matches.value_of("pattern") {
+        Some("random") => Pattern::Random,
+        Some("zeros") => Pattern::Zeros,
+        Some("ones") => Pattern::Ones,
+        _ => Pattern::Counter,
     }
+}
 
-    #[test]
-    fn test_compare_self() {
-        let test_frame: CANFrame = CANFrame::new(0x77, &[1, 2, 3, 4, 5, 6, 7, 8], false, false)
-            .unwrap();
-        assert!(Host::compare_frame(test_frame, test_frame, 0).unwrap());
+/// Reads a test ID (in hex) from the program arguments, exits on invalid IDs
+/// # Arguments
+/// * 'arg_matches' - parsed program arguments
+/// * 'name' - name of the argument
+/// * 'default' - ID used if the argument is not given
+fn test_id(arg_matches: &ArgMatches, name: &str, default: u32) -> CanId {
+    let id: u32 = match arg_matches.value_of(name) {
+        Some(value) => match u32::from_str_radix(value.trim_start_matches("0x"), 16) {
+            Ok(id) => id,
+            Err(_) => {
+                log::error!("No valid {} given!", name.replace('_', " "));
+                process::exit(1);
+            },
+        },
+        None => default,
+    };
+    let can_id = if arg_matches.is_present("extended") {
+        CanId::extended(id)
+    } else {
+        CanId::standard(id)
+    };
+    match can_id {
+        Ok(can_id) => can_id,
+        Err(_) => {
+            log::error!("{} {:X} out of range!", name.replace('_', " "), id);
+            process::exit(1);
+        },
     }
+}
 
-    #[test]
-    fn test_compare_incremented_received() {
-        let expected_frame: CANFrame = CANFrame::new(0x77, &[1, 2, 3, 4, 5, 6, 7, 8], false, false)
-            .unwrap();
-        let test_frame: CANFrame = CANFrame::new(0x78, &[2, 3, 4, 5, 6, 7, 8, 9], false, false)
-            .unwrap();
-        assert!(Host::compare_frame(expected_frame, test_frame, 1).unwrap());
+/// Writes a report to the given file, exits on errors
+/// # Arguments
+/// * 'path' - file to write
+/// * 'report' - content of the report
+fn write_report(path: &str, report: &str) {
+    if let Err(e) = fs::write(path, report) {
+        log::error!("Could not write report to {}! Reason: {}", path, e);
+        process::exit(1);
     }
+}
 
-    impl fmt::Display for HostError {
-        //TODO: todo
-        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-            write!(f, "{}", self.details)
-        }
+/// Writes the requested reports of the host, exits on errors
+/// # Arguments
+/// * 'arg_matches' - parsed program arguments
+/// * 'config' - settings of the host
+/// * 'result' - result of the host
+fn write_reports(arg_matches: &ArgMatches, config: &host::HostConfig, result: &Result<host::HostSummary, host::HostFailure>) {
+    if let Some(path) = arg_matches.value_of("json") {
+        write_report(path, &report::json_report(config, result));
     }
-
-    #[test]
-    fn test_false_incremented_received() {
-        let expected_frame: CANFrame = CANFrame::new(0x77, &[1, 2, 3, 4, 5, 6, 7, 8], false, false)
-            .unwrap();
-        let test_frame: CANFrame = CANFrame::new(0x78, &[2, 3, 4, 5, 7, 8, 9, 10], false, false)
-            .unwrap();
-        assert_eq!(false, Host::compare_frame(expected_frame, test_frame, 1).unwrap());
+    if let Some(path) = arg_matches.value_of("junit") {
+        write_report(path, &report::junit_report(config, result));
     }
 }
 
-mod dut {
-    use log;
-    use std::fmt;
-    use socketcan::{CANFrame, CANSocket};
-
-    pub struct Dut {
-        socket: CANSocket,
-    }
-    
-    const CAN_MSG_ID: u32 = 0x77;
-
-    #[derive(Debug)]
-    pub struct DutError {
-        details: String
+/// Reads the settings of the host from the program arguments, exits on invalid values
+/// # Arguments
+/// * 'arg_matches' - parsed program arguments
+/// * 'host_id' - ID of the host frames
+/// * 'dut_id' - ID of the DUT responses
+/// * 'duration' - duration of the test
+fn host_config(arg_matches: &ArgMatches, host_id: CanId, dut_id: CanId, duration: Option<Duration>) -> host::HostConfig {
+    let mut config: host::HostConfig = host::HostConfig {
+        fd: arg_matches.is_present("fd"),
+        brs: arg_matches.is_present("brs"),
+        host_id,
+        dut_id,
+        tolerant: arg_matches.is_present("tolerant"),
+        duration,
+        pattern: pattern(arg_matches),
+        random_length: arg_matches.is_present("random_length"),
+        ..host::HostConfig::default()
+    };
+    config.seed = match arg_matches.value_of("seed") {
+        Some(value) => match value.parse() {
+            Ok(seed) => seed,
+            Err(_) => {
+                log::error!("No valid seed given!");
+                process::exit(1);
+            },
+        },
+        None => SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |time| time.as_nanos() as u64),
+    };
+    if config.pattern == Pattern::Random || config.random_length {
+        // printed to reproduce the test frames with --seed
+        println!("Random seed: {}", config.seed);
     }
-
-    impl DutError {
-        fn new(msg: &str) -> DutError {
-            DutError{details: msg.to_string()
-            }
-        }
+    if let Some(value) = arg_matches.value_of("frame_count") {
+        config.frame_count = match value.parse() {
+            Ok(count) => count,
+            Err(_) => {
+                log::error!("No valid test frame count given!");
+                process::exit(1);
+            },
+        };
     }
-
-    impl fmt::Display for DutError {
-        //TODO: todo
-        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-            write!(f, "{}", self.details)
-        }
+    if let Some(value) = arg_matches.value_of("inflight") {
+        config.inflight_count = match value.parse() {
+            Ok(count) if count > 0 => count,
+            _ => {
+                log::error!("No valid inflight count given!");
+                process::exit(1);
+            },
+        };
     }
-    
-    fn check_frame(frame: CANFrame) -> Result<bool, DutError> {
-        if  frame.id() != CAN_MSG_ID {
-            Err(DutError::new("Received message ID mismatch!"))
-        } else {
-            for i in 1..frame.data().len() {
-                let byte: u8 = if frame.data()[i] == 0  {
-                    0xff
-                } else {
-                    frame.data()[i] - 1
-                };
-                if frame.data()[i - 1] != byte {
-                    log::debug!("Received data bytes: {:x?}", frame.data());
-                    return Err(DutError::new("Received data byte mismatch!"));
-                }
-            }
-            Ok(true)
-        }
+    if let Some(value) = arg_matches.value_of("timeout") {
+        config.response_timeout = match value.parse() {
+            Ok(timeout) if timeout > 0 => Duration::from_millis(timeout),
+            _ => {
+                log::error!("No valid response timeout given!");
+                process::exit(1);
+            },
+        };
     }
-
-    impl Dut {
-        pub fn new(socket_name: &str) -> Result<Dut, DutError> {
-            let can: CANSocket = match CANSocket::open(socket_name) {
-                Ok(socket) => socket,
-                Err(_) => return Err(DutError::new("Could not open socket")),
-            };
-            let dut = Dut {
-                socket: can,
-            };
-            Ok(dut)
-        }
-
-        pub fn run(self) {
-            let mut frame_count: usize = 0;
-            loop {
-                let received_frame: CANFrame = match self.socket.read_frame() {
-                    Ok(frame) => {
-                        log::debug!{"Received frame: {:x?}", &frame};
-                        frame_count += 1;
-                        frame
-                    },
-                    Err(e) => {
-                        log::error!("Error receiving frame: {}", e);
-                        break;
-                    },
-                };
-                match check_frame(received_frame) {
-                    Ok(result) => {
-                        if result {
-                            let frame: CANFrame = match super::increment_frame(received_frame) {
-                                None => {
-                                    log::error!("Error incrementing frame for sending!");
-                                    break;
-                                },
-                                Some(f) => f,
-                            };
-                            match self.socket.write_frame_insist(&frame) {
-                                //TODO: implement wait time for interleaving mode, i.e. a wait time
-                                Ok(_) => continue,
-                                Err(e) => {
-                                    log::error!("Error while writing frame! {}", e);
-                                    break;
-                                },
-                            }
-                        } else {
-                            log::error!("Frame check did not pass!");
-                            break;
-                        }
-                    },
-                    Err(_) => log::error!("Error occured checking frame!"),
-                };
-            }
-            log::info!("Received {} frames.", frame_count);
-        }
+    if let Some(value) = arg_matches.value_of("progress") {
+        config.progress_interval = match value.parse() {
+            Ok(interval) if interval > 0 => Some(Duration::from_millis(interval)),
+            _ => {
+                log::error!("No valid progress interval given!");
+                process::exit(1);
+            },
+        };
     }
-
-    #[test]
-    fn test_correct_frame_check() {
-        let correct_frame: CANFrame = CANFrame::new(0x77, &[1, 2, 3], false, false)
-            .unwrap();
-        assert!(check_frame(correct_frame)
-            .unwrap()
-        );
+    if let Some(value) = arg_matches.value_of("gap") {
+        config.frame_gap = micros(value, "frame gap");
     }
-
-    #[test]
-    fn test_false_id_frame_check() {
-        let false_id_frame: CANFrame = CANFrame::new(0x123, &[1, 2, 3], false, false)
-            .unwrap();
-        assert_eq!(true, check_frame(false_id_frame)
-            .is_err()
-        );
+    if let Some(value) = arg_matches.value_of("burst") {
+        config.burst_length = match value.parse() {
+            Ok(length) => length,
+            Err(_) => {
+                log::error!("No valid burst length given!");
+                process::exit(1);
+            },
+        };
     }
-
-    #[test]
-    fn test_false_data_frame_check() {
-        let false_data_frame: CANFrame = CANFrame::new(0x77, &[1, 1, 3], false, false)
-            .unwrap();
-        assert_eq!(true, check_frame(false_data_frame)
-            .is_err()
-        );
+    if let Some(value) = arg_matches.value_of("burst_gap") {
+        config.burst_gap = micros(value, "burst gap");
     }
+    config
+}
 
-    #[test]
-    fn test_frame_increment() {
-        let host_frame: CANFrame = CANFrame::new(0x77, &[1, 2, 3, 4, 5, 6, 7, 8], false, false)
-            .unwrap();
-        let incremented_frame: CANFrame = super::increment_frame(host_frame)
-            .unwrap();
-        
-        assert_eq!(0x78, incremented_frame.id());
-        assert_eq!(&[2, 3, 4, 5, 6, 7, 8, 9], incremented_frame.data());
+/// Reads the settings of the DUT from the program arguments, exits on invalid values
+/// # Arguments
+/// * 'arg_matches' - parsed program arguments
+/// * 'host_id' - ID of the host frames
+/// * 'dut_id' - ID of the DUT responses
+/// * 'duration' - time after which the DUT stops
+fn dut_config(arg_matches: &ArgMatches, host_id: CanId, dut_id: CanId, duration: Option<Duration>) -> dut::DutConfig {
+    dut::DutConfig {
+        fd: arg_matches.is_present("fd"),
+        host_id,
+        dut_id,
+        tolerant: arg_matches.is_present("tolerant"),
+        reply_delay: arg_matches.value_of("reply_delay")
+            .map_or(Duration::ZERO, |value| micros(value, "reply delay")),
+        duration,
+        pattern: pattern(arg_matches),
+        ..dut::DutConfig::default()
     }
+}
 
-    #[test]
-    fn test_partial_frame_increment() {
-        // This should not occur during normal echo test, but it doesn't hurt to test it
-        let host_frame: CANFrame = CANFrame::new(0x77, &[1, 2, 3, 4], false, false)
-            .unwrap();
-        let incremented_frame: CANFrame = super::increment_frame(host_frame)
-            .unwrap();
-        
-        assert_eq!(0x78, incremented_frame.id());
-        assert_eq!(&[2, 3, 4, 5], incremented_frame.data());
+/// Opens the host on the given socket, exits on errors
+fn open_host(socket_name: &str, config: host::HostConfig) -> host::Host {
+    match host::Host::new(socket_name, config) {
+        Ok(h) => h,
+        Err(e) => {
+            log::error!("Could not instantiate Host/Generator! Reason: {}", e);
+            process::exit(1);
+        },
     }
+}
 
-    #[test]
-    fn test_overflow_increment() {
-        // test for problem fixed in commit '1af70af034f7c4c20ad63a5e3127875b9bee6533'
-        let host_frame: CANFrame = CANFrame::new(0x77, &[0xf9, 0xfa , 0xfb, 0xfc, 0xfd, 0xfe, 0xff, 0x00], false, false)
-            .unwrap();
-        let incremented_frame: CANFrame = super::increment_frame(host_frame)
-            .unwrap();
-
-        assert_eq!(&[0xfa, 0xfb, 0xfc, 0xfd, 0xfe, 0xff, 0x00, 0x01], incremented_frame.data());
+/// Opens the DUT on the given socket, exits on errors
+fn open_dut(socket_name: &str, config: dut::DutConfig) -> dut::Dut {
+    match dut::Dut::new(socket_name, config) {
+        Ok(r) => r,
+        Err(e) => {
+            log::error!("Could not instantiate DUT! Reason: {}", e);
+            process::exit(1);
+        },
     }
 }
 
@@ -402,7 +229,8 @@ pub fn main() {
                                 Arg::with_name("socket")
                                     .help("Name of CAN socket")
                                     .index(1)
-                                    .required(true),
+                                    .required_unless("loop")
+                                    .conflicts_with("loop"),
                             )
                             .arg(
                                 Arg::with_name("generator")
@@ -410,6 +238,18 @@ pub fn main() {
                                     .short("g")
                                     .long("generator"),
                             )
+                            .arg(
+                                Arg::with_name("loop")
+                                    .help("runs host and DUT in one process, the host on the first and the DUT on the second socket")
+                                    .long("loop")
+                                    .takes_value(true)
+                                    .number_of_values(2)
+                                    .value_names(&["host_socket", "dut_socket"]),
+                            )
+                            .group(
+                                ArgGroup::with_name("host_mode")
+                                    .args(&["generator", "loop"]),
+                            )
                             .arg(
                                 Arg::with_name("verbosity")
                                     .help("sets verbose level")
@@ -422,14 +262,136 @@ pub fn main() {
                                     .help("inflight count")
                                     .short("f")
                                     .takes_value(true)
-                                    .requires("generator"),
+                                    .requires("host_mode"),
                             )
                             .arg(
                                 Arg::with_name("frame_count")
-                                .help("test frame count")
+                                .help("test frame count (0 runs until an error occurs)")
                                 .short("l")
                                 .takes_value(true)
-                                .requires("generator"),
+                                .requires("host_mode"),
+                            )
+                            .arg(
+                                Arg::with_name("timeout")
+                                .help("response timeout in ms")
+                                .short("t")
+                                .long("timeout")
+                                .takes_value(true)
+                                .requires("host_mode"),
+                            )
+                            .arg(
+                                Arg::with_name("progress")
+                                .help("prints the progress every given ms")
+                                .short("p")
+                                .long("progress")
+                                .takes_value(true)
+                                .requires("host_mode"),
+                            )
+                            .arg(
+                                Arg::with_name("duration")
+                                .help("stops the test after the given seconds")
+                                .long("duration")
+                                .takes_value(true),
+                            )
+                            .arg(
+                                Arg::with_name("gap")
+                                .help("time to wait after sending a frame in µs (default 1000, 0 sends at full bus load)")
+                                .long("gap")
+                                .takes_value(true)
+                                .requires("host_mode"),
+                            )
+                            .arg(
+                                Arg::with_name("burst")
+                                .help("count of frames sent before waiting the burst gap (default 33, 0 disables bursts)")
+                                .long("burst")
+                                .takes_value(true)
+                                .requires("host_mode"),
+                            )
+                            .arg(
+                                Arg::with_name("burst_gap")
+                                .help("time to wait after sending a burst of frames in µs (default 3000)")
+                                .long("burst-gap")
+                                .takes_value(true)
+                                .requires("host_mode"),
+                            )
+                            .arg(
+                                Arg::with_name("reply_delay")
+                                .help("DUT waits the given µs before answering a frame")
+                                .long("reply-delay")
+                                .takes_value(true)
+                                .conflicts_with("generator"),
+                            )
+                            .arg(
+                                Arg::with_name("json")
+                                .help("writes a JSON summary to the given file")
+                                .long("json")
+                                .takes_value(true)
+                                .requires("host_mode"),
+                            )
+                            .arg(
+                                Arg::with_name("junit")
+                                .help("writes a JUnit XML report to the given file")
+                                .long("junit")
+                                .takes_value(true)
+                                .requires("host_mode"),
+                            )
+                            .arg(
+                                Arg::with_name("tolerant")
+                                .help("counts errors and resynchronizes instead of stopping at the first error")
+                                .long("tolerant"),
+                            )
+                            .arg(
+                                Arg::with_name("pattern")
+                                .help("data pattern of the test frames (host and DUT)")
+                                .long("pattern")
+                                .takes_value(true)
+                                .possible_values(&["counter", "random", "zeros", "ones"]),
+                            )
+                            .arg(
+                                Arg::with_name("seed")
+                                .help("seed of random data and random lengths (default from the current time)")
+                                .long("seed")
+                                .takes_value(true)
+                                .requires("host_mode"),
+                            )
+                            .arg(
+                                Arg::with_name("random_length")
+                                .help("sends frames with random data lengths")
+                                .long("random-length")
+                                .requires("host_mode"),
+                            )
+                            .arg(
+                                Arg::with_name("fd")
+                                .help("CAN FD mode")
+                                .short("d")
+                                .long("fd"),
+                            )
+                            .arg(
+                                Arg::with_name("brs")
+                                .help("bit rate switch for CAN FD frames")
+                                .short("b")
+                                .long("brs")
+                                .requires_all(&["host_mode", "fd"]),
+                            )
+                            .arg(
+                                Arg::with_name("extended")
+                                .help("use 29 bit extended test IDs")
+                                .short("e")
+                                .long("extended"),
+                            )
+                            .arg(
+                                Arg::with_name("host_id")
+                                .help("ID of the host frames in hex (default 77)")
+                                .short("i")
+                                .long("host-id")
+                                .takes_value(true),
+                            )
+                            .arg(
+                                Arg::with_name("dut_id")
+                                .help("ID of the DUT responses in hex (default 78)")
+                                .short("r")
+                                .long("dut-id")
+                                .takes_value(true),
                             )
                             .get_matches();
     
@@ -455,6 +417,31 @@ pub fn main() {
                 .init()
                 .unwrap(),
     }
+    let duration: Option<Duration> = arg_matches.value_of("duration").map(|value| match value.parse() {
+        Ok(seconds) if seconds > 0 => Duration::from_secs(seconds),
+        _ => {
+            log::error!("No valid duration given!");
+            process::exit(1);
+        },
+    });
+    let host_id: CanId = test_id(&arg_matches, "host_id", DEFAULT_HOST_ID);
+    let dut_id: CanId = test_id(&arg_matches, "dut_id", DEFAULT_DUT_ID);
+    if let Some(sockets) = arg_matches.values_of("loop") {
+        let sockets: Vec<&str> = sockets.collect();
+        log::info!("Starting host on {} and DUT on {}.", sockets[0], sockets[1]);
+        let config: host::HostConfig = host_config(&arg_matches, host_id, dut_id, duration);
+        let mut host: host::Host = open_host(sockets[0], config.clone());
+        host.on_progress(|progress| println!("{}", progress));
+        // the DUT is stopped after the host awaited its frames in flight
+        let dut: dut::Dut = open_dut(sockets[1], dut_config(&arg_matches, host_id, dut_id, None));
+        if let Err(e) = shutdown::stop_on_termination(host.stop_flag()) {
+            log::warn!("Could not install signal handler! {}", e);
+        }
+        let result: local::LocalResult = local::run_local(host, dut);
+        write_reports(&arg_matches, &config, &result.host);
+        println!("{}", result);
+        process::exit(result.exit_code());
+    }
     let socket_name: &str = match arg_matches.value_of("socket") {
         Some(s) => s,
         None => {
@@ -463,26 +450,39 @@ pub fn main() {
         },
     };
     if arg_matches.is_present("generator") {
-        let host: host::Host = match host::Host::new(socket_name, DEFAULT_INFLIGHT_COUNT, 0) {
-            Ok(h) => h,
-            Err(e) => {
-                log::error!("Could not instantiate Host/Generator! Reason: {}", e);
-                process::exit(1);
+        let config: host::HostConfig = host_config(&arg_matches, host_id, dut_id, duration);
+        let mut host: host::Host = open_host(socket_name, config.clone());
+        host.on_progress(|progress| println!("{}", progress));
+        if let Err(e) = shutdown::stop_on_termination(host.stop_flag()) {
+            log::warn!("Could not install signal handler! {}", e);
+        }
+        let result: Result<host::HostSummary, host::HostFailure> = host.run();
+        write_reports(&arg_matches, &config, &result);
+        match result {
+            Ok(summary) => {
+                println!("{}", summary);
+                process::exit(0);
             },
-        };
-        host.run();
-        process::exit(0);
+            Err(failure) => {
+                println!("{}", failure);
+                process::exit(failure.reason.exit_code());
+            },
+        }
     } else {
         log::info!("Starting as DUT.");
-        let dut: dut::Dut = match dut::Dut::new(socket_name) {
-            Ok(r) => r,
-            Err(e) => {
-                log::error!("Could not instantiate DUT! Reason: {}", e);
-                process::exit(1);
+        let dut: dut::Dut = open_dut(socket_name, dut_config(&arg_matches, host_id, dut_id, duration));
+        if let Err(e) = shutdown::stop_on_termination(dut.stop_flag()) {
+            log::warn!("Could not install signal handler! {}", e);
+        }
+        match dut.run() {
+            Ok(summary) => {
+                println!("{}", summary);
+                process::exit(0);
             },
-        };
-        //TODO: give run Result as return type to exit program with exit code accordingly
-        dut.run();
-        process::exit(0);
+            Err(failure) => {
+                println!("{}", failure);
+                process::exit(failure.reason.exit_code());
+            },
+        }
     }
 }
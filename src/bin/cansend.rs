@@ -1,162 +1,330 @@
+use can_utils::bcm::BcmSocket;
+use can_utils::cyclic::{parse_interval, JitterStats, Schedule};
+use can_utils::fd;
+use can_utils::frame::{parse_frame_line, parse_frame_string, AnyFrame};
+use can_utils::shutdown;
 use clap::{App, Arg};
 use log::LevelFilter;
-use socketcan::{CANFrame, CANSocket};
-use std::{convert::TryInto, process};
+use socketcan::CANSocket;
+use std::convert::TryFrom;
+use std::io::{self, BufRead};
+use std::os::unix::io::AsRawFd;
+use std::process;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 use simple_logger::SimpleLogger;
 
-const CAN_MSG_SIZE: usize = 8;
+/// Default time to wait for space in the TX queue
+const DEFAULT_TIMEOUT: Duration = Duration::from_millis(1000);
 
-//TODO implement own error values to return
-fn data_string_to_hex(input: String) -> Option<Vec<u8>> {
-    let str: &str  = &input[..];
-    if str.len() % 2 != 0 || str.len() > (CAN_MSG_SIZE * 2) {
-        None
-    } else {
-        let result: Vec<u8> = (0..str.len())
-            .step_by(2)
-            .map(|o| u8::from_str_radix(&str[o..o + 2], 16)
-            .unwrap())
-            .collect();
-        Some(result)
-    }
+/// Opened CAN socket with the settings for sending frames
+struct Sender {
+    can_socket: CANSocket,
+    fd_enabled: bool,
+    timeout: Option<Duration>,
 }
 
-fn id_string_to_hex(input: String) -> Option<u32> {
-    let id_string: String;
-    if input.len() % 2 != 0 {
-        id_string = format!("{}{}", "0", input);
-    } else {
-        id_string = input;
-    }
-    let mut result: Vec<u8> = (0..id_string.len())
-        .step_by(2)
-        .map(|o| u8::from_str_radix(&id_string[o..o + 2], 16)
-        .unwrap())
-        .collect();
-    if result.len() != 4 {
-        for _i in 0..(4 - result.len()) {
-            result.insert(0, 0);
+impl Sender {
+    /// Creates a sender on an opened socket
+    /// # Arguments
+    /// * 'can_socket' - opened CAN socket
+    /// * 'timeout' - maximum time to wait for space in the TX queue, `None` retries forever
+    fn new(can_socket: CANSocket, timeout: Option<Duration>) -> Sender {
+        Sender { can_socket, fd_enabled: false, timeout }
+    }
+
+    /// Writes a frame to the socket, exits on errors
+    ///
+    /// CAN FD frames are enabled on the socket with the first CAN FD frame.
+    /// # Arguments
+    /// * 'frame' - frame to send
+    fn send(&mut self, frame: &AnyFrame) {
+        if let AnyFrame::Fd(_) = frame {
+            if !self.fd_enabled {
+                if let Err(error) = fd::set_fd_frames(&self.can_socket, true) {
+                    log::error!("Could not enable CAN FD frames on socket! Error: {}", error);
+                    process::exit(1);
+                }
+                self.fd_enabled = true;
+            }
+        }
+        let write_result = match (self.timeout, frame) {
+            (Some(timeout), frame) => fd::write_frame_timeout(&self.can_socket, frame, timeout),
+            // blocking write functions
+            (None, AnyFrame::Classic(frame)) => self.can_socket.write_frame_insist(frame),
+            (None, AnyFrame::Fd(frame)) => fd::write_fd_frame_insist(&self.can_socket, frame),
+        };
+        if let Err(error) = write_result {
+            log::error!("Error sending frame {}! Error: {}", frame, error);
+            process::exit(1);
         }
-        log::debug!("Result id parsing: {:x?}", result);
-    } else {
-        log::debug!("Result id parsing: {:x?}", result);
     }
-    Some(u32::from_be_bytes(result[..].try_into().unwrap()))
 }
 
-//TODO implement own error values to return
-fn parse_frame_string(frame_string: String) -> Option<CANFrame> {
-    let frame_tokens: Vec<String> = frame_string
-        .split("#")
-        .map(|s| s.to_string())
-        .collect();
-    log::debug!("Frame tokens: {:?}", frame_tokens);
-    if frame_tokens.len() != 2 {
-        return None;
-    }
-    let frame_id: u32 = id_string_to_hex(frame_tokens[0].to_owned()).unwrap();
-    let frame_data: String = frame_tokens[1].to_owned();
-    if frame_data == "R" {
-        // set RTR flag in frame
-        let frame: CANFrame = 
-            CANFrame::new(frame_id, &[], true, false)
-                .expect("Error creating CAN-Remote-Frame");
-        Some(frame)
-    } else {
-        let data_bytes: &[u8] = &(data_string_to_hex(frame_data).unwrap())[..];
-        log::debug!("Frame bytes: {:x?}", data_bytes);
-        let frame: CANFrame =
-            CANFrame::new(frame_id, data_bytes, false, false)
-                .expect("Error creating CAN-Frame!");
-        Some(frame)
+/// Sends the frames read from stdin line by line, exits on errors
+/// # Arguments
+/// * 'sender' - socket to send the frames on
+fn send_stdin_frames(sender: &mut Sender) {
+    let stdin = io::stdin();
+    for (index, line) in stdin.lock().lines().enumerate() {
+        let line: String = match line {
+            Ok(line) => line,
+            Err(error) => {
+                log::error!("Error reading stdin! Error: {}", error);
+                process::exit(1);
+            },
+        };
+        match parse_frame_line(&line) {
+            Ok(Some(frame)) => sender.send(&frame),
+            Ok(None) => continue,
+            Err(error) => {
+                log::error!("Error parsing frame-string in line {}! Error: {}\n{}", index + 1, error, error.annotate(&line));
+                process::exit(1);
+            },
+        }
     }
 }
 
-#[test]
-fn test_frame_parsing() {
-    let test_frame: String = "123#cafe"
-        .to_owned();
-    let exptected_frame: CANFrame = CANFrame::new(0x123, &[0xca, 0xfe], false, false)
-        .unwrap();
-    let created_frame: CANFrame = parse_frame_string(test_frame)
-        .unwrap();
-    assert_eq!(exptected_frame.id(), created_frame.id());
-    assert_eq!(exptected_frame.is_extended(), created_frame.is_extended());
-    assert_eq!(exptected_frame.is_rtr(), created_frame.is_rtr());
-    for i in 0..exptected_frame.data().len() {
-        assert_eq!(exptected_frame.data()[i], created_frame.data()[i]);
+/// Sends the frames repeatedly with drift-free timing and prints the achieved jitter, exits on errors
+///
+/// Stops early on SIGINT or SIGTERM.
+/// # Arguments
+/// * 'sender' - socket to send the frames on
+/// * 'frames' - frames sent in every cycle
+/// * 'count' - count of cycles, `0` sends until interrupted
+/// * 'interval' - time between the start of two cycles
+/// * 'counter_byte' - index of the data byte incremented with every cycle
+fn send_cyclic(sender: &mut Sender, frames: &[AnyFrame], count: usize, interval: Duration, counter_byte: Option<usize>) {
+    let stop: Arc<AtomicBool> = Arc::new(AtomicBool::new(false));
+    if let Err(error) = shutdown::stop_on_termination(stop.clone()) {
+        log::warn!("Could not install signal handler! Error: {}", error);
+    }
+    let mut schedule: Schedule = Schedule::new(interval);
+    let mut jitter: JitterStats = JitterStats::new();
+    let mut cycle: usize = 0;
+    while count == 0 || cycle < count {
+        let delay: Duration = schedule.wait();
+        if stop.load(Ordering::SeqCst) {
+            break;
+        }
+        jitter.add(delay);
+        for frame in frames.iter() {
+            let frame: AnyFrame = match counter_byte {
+                Some(index) => {
+                    let mut data: Vec<u8> = frame.data().to_vec();
+                    data[index] = data[index].wrapping_add(cycle as u8);
+                    // same length as the parsed frame, so creating it cannot fail
+                    frame.with_data(&data)
+                        .expect("Error creating frame with counter byte!")
+                },
+                None => *frame,
+            };
+            sender.send(&frame);
+        }
+        cycle += 1;
     }
+    println!("Sent {} frames in {} cycles, elapsed time: {:.3} s\n{}",
+        cycle * frames.len(), cycle, schedule.elapsed().as_secs_f64(), jitter);
 }
 
-#[test]
-fn test_frame_parsing_remote() {
-    let test_frame: String = "444#R"
-        .to_owned();
-    let exptected_frame: CANFrame = CANFrame::new(0x444, &[], true, false)
-        .unwrap();
-    let created_frame: CANFrame = parse_frame_string(test_frame)
-        .unwrap();
-    assert_eq!(exptected_frame.id(), created_frame.id());
-    assert_eq!(exptected_frame.is_rtr(), created_frame.is_rtr());
-    assert_eq!(exptected_frame.data().len(), created_frame.data().len());
-}
+/// Maximum time to wait for notifications or updates before checking for a stop request
+const STOP_POLL_INTERVAL: Duration = Duration::from_millis(100);
 
-#[test]
-fn test_frame_parsing_extended() {
-    let test_frame: String = "111fff#0102030405060708"
-        .to_owned();
-    let exptected_frame: CANFrame = CANFrame::new(0x111fff, &[0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08], false, false)
-        .unwrap();
-    let created_frame: CANFrame = parse_frame_string(test_frame)
-        .unwrap();
-    assert_eq!(exptected_frame.id(), created_frame.id());
-    assert_eq!(exptected_frame.is_extended(), created_frame.is_extended());
-    assert!(created_frame.is_extended());
-    assert_eq!(exptected_frame.is_rtr(), created_frame.is_rtr());
-    for i in 0..exptected_frame.data().len() {
-        assert_eq!(exptected_frame.data()[i], created_frame.data()[i]);
+/// Updates the data of a cyclic job with a line read from stdin
+///
+/// Invalid lines are reported and skipped, so the running jobs are not interrupted.
+/// # Arguments
+/// * 'bcm_socket' - broadcast manager socket of the jobs
+/// * 'frames' - frames of the jobs
+/// * 'count' - count the jobs were set up with
+/// * 'line' - line read from stdin
+fn update_bcm_job(bcm_socket: &BcmSocket, frames: &[AnyFrame], count: u32, line: &str) {
+    let frame: AnyFrame = match parse_frame_line(line) {
+        Ok(Some(frame)) => frame,
+        Ok(None) => return,
+        Err(error) => {
+            log::warn!("Error parsing frame-string! Error: {}\n{}", error, error.annotate(line));
+            return;
+        },
+    };
+    if !frames.iter().any(|job| job.can_id() == frame.can_id() && job.is_fd() == frame.is_fd()) {
+        log::warn!("No cyclic job for frame {}!", frame);
+    } else if let Err(error) = bcm_socket.tx_update(&frame, count) {
+        log::warn!("Could not update frame {}! Error: {}", frame, error);
     }
 }
 
-#[test]
-fn test_id_parsing_even() {
-    let frame_id: u32 = id_string_to_hex("ff7123".to_owned()).unwrap();
-    assert_eq!(0xff7123, frame_id);
-}
-
-#[test]
-fn test_id_parsing_odd() {
-    let frame_id: u32 = id_string_to_hex("1ff7123".to_owned()).unwrap();
-    assert_eq!(0x1ff7123, frame_id);
+/// Hands the frames to the broadcast manager of the kernel for cyclic transmission, exits on errors
+///
+/// Every frame gets its own job. The jobs are deleted when they sent all
+/// frames of the count, on SIGINT or SIGTERM.
+/// # Arguments
+/// * 'can_socket_name' - name of the CAN interface
+/// * 'frames' - frames sent cyclically, with distinct IDs
+/// * 'count' - count of frames sent by every job, `0` sends until interrupted
+/// * 'interval' - time between two frames of a job
+/// * 'update' - frames read from stdin update the data of the job with the same ID
+fn send_bcm(can_socket_name: &str, frames: &[AnyFrame], count: u32, interval: Duration, update: bool) {
+    let bcm_socket: BcmSocket = match BcmSocket::open(can_socket_name) {
+        Ok(socket) => socket,
+        Err(error) => {
+            log::debug!("Given name of socket: {}", can_socket_name);
+            log::error!("Could not open broadcast manager socket! Error: {}", error);
+            process::exit(1);
+        },
+    };
+    let stop: Arc<AtomicBool> = Arc::new(AtomicBool::new(false));
+    if let Err(error) = shutdown::stop_on_termination(stop.clone()) {
+        log::warn!("Could not install signal handler! Error: {}", error);
+    }
+    let mut expiring: bool = false;
+    for frame in frames.iter() {
+        match bcm_socket.tx_setup(frame, interval, count) {
+            Ok(expires) => expiring = expires,
+            Err(error) => {
+                log::error!("Could not set up cyclic transmission of frame {}! Error: {}", frame, error);
+                process::exit(1);
+            },
+        }
+    }
+    let start: Instant = Instant::now();
+    let mut expired: usize = 0;
+    let mut read_stdin: bool = update;
+    let mut pending: Vec<u8> = Vec::new();
+    // jobs sending a single frame are done after the setup
+    while !stop.load(Ordering::SeqCst) && (count == 0 || (expiring && expired < frames.len())) {
+        let mut poll_fds: [libc::pollfd; 2] = [
+            libc::pollfd { fd: bcm_socket.as_raw_fd(), events: libc::POLLIN, revents: 0 },
+            libc::pollfd { fd: libc::STDIN_FILENO, events: libc::POLLIN, revents: 0 },
+        ];
+        let poll_count: libc::nfds_t = if read_stdin { 2 } else { 1 };
+        let poll_rv = unsafe {
+            libc::poll(poll_fds.as_mut_ptr(), poll_count, STOP_POLL_INTERVAL.as_millis() as libc::c_int)
+        };
+        if poll_rv < 0 {
+            let error: io::Error = io::Error::last_os_error();
+            if error.kind() == io::ErrorKind::Interrupted {
+                continue;
+            }
+            log::error!("Error waiting for the broadcast manager! Error: {}", error);
+            process::exit(1);
+        }
+        if poll_fds[0].revents & libc::POLLIN != 0 {
+            match bcm_socket.read_expired() {
+                Ok(Some(_)) => expired += 1,
+                Ok(None) => {},
+                Err(error) => {
+                    log::error!("Error reading from broadcast manager! Error: {}", error);
+                    process::exit(1);
+                },
+            }
+        }
+        if read_stdin && poll_fds[1].revents != 0 {
+            // read directly, buffered lines would not wake up poll
+            let mut buffer: [u8; 1024] = [0; 1024];
+            let read_rv = unsafe {
+                libc::read(libc::STDIN_FILENO, buffer.as_mut_ptr() as *mut libc::c_void, buffer.len())
+            };
+            if read_rv > 0 {
+                pending.extend_from_slice(&buffer[..read_rv as usize]);
+            } else {
+                // end of input (or error), the jobs keep running
+                read_stdin = false;
+                pending.push(b'\n');
+            }
+            while let Some(position) = pending.iter().position(|&byte| byte == b'\n') {
+                let line: Vec<u8> = pending.drain(..=position).collect();
+                update_bcm_job(&bcm_socket, frames, count, String::from_utf8_lossy(&line).trim_end());
+            }
+        }
+    }
+    for frame in frames.iter() {
+        if let Err(error) = bcm_socket.tx_delete(frame) {
+            log::warn!("Could not delete cyclic transmission of frame {}! Error: {}", frame, error);
+        }
+    }
+    println!("Deleted {} cyclic jobs, elapsed time: {:.3} s", frames.len(), start.elapsed().as_secs_f64());
 }
 
-
-/// Sets a single CAN frame on a given bus (does not support CAN-FD)
+/// Sets CAN or CAN FD frames on a given bus
 /// # Arguments
 /// * 'args' - program arguments
 ///
 /// # Examples
 /// ```
 /// cansend  can0 123#cafe
+/// cansend  can0 123##1cafe
+/// cansend  can0 123#01 123#02 123#03
+/// cansend  can0 - < frames.txt
+/// cansend  can0 123#00 --interval 100ms --counter-byte 0
+/// cansend  can0 123#cafe --timeout 100
+/// cansend  can0 123#00 --bcm --interval 100ms --update
 /// ```
 ///
 fn main() {
     let arg_matches = App::new("cansend")
                             .version("0.1.1")
                             .author("Raphael Nissl")
-                            .about("Program sets a CAN-Frame on a bus with given ID and data (does not support CAN FD protocol)")
+                            .about("Program sets CAN-Frames on a bus with given ID and data (CAN FD frames are given as <id>##<flags><data>)")
                             .arg(
                                 Arg::with_name("socket")
                                     .help("name of CAN socket")
                                     .index(1)
-                                    .requires("frame")
                                     .required(true),
                             )
                             .arg(
                                 Arg::with_name("frame")
-                                    .help("Frame consisting of ID and data")
+                                    .help("Frames consisting of ID and data, sent in the given order ('-' reads frames from stdin)")
                                     .index(2)
-                                    .required(true),
+                                    .multiple(true)
+                                    .required_unless("stdin"),
+                            )
+                            .arg(
+                                Arg::with_name("stdin")
+                                    .help("reads one frame per line from stdin, blank lines and lines starting with '#' are skipped")
+                                    .long("stdin")
+                                    .conflicts_with_all(&["frame", "count", "interval", "counter_byte"]),
+                            )
+                            .arg(
+                                Arg::with_name("count")
+                                    .help("count of cycles sending all frames, 0 sends until interrupted (default 1, with --interval 0)")
+                                    .short("n")
+                                    .long("count")
+                                    .takes_value(true),
+                            )
+                            .arg(
+                                Arg::with_name("interval")
+                                    .help("time between two cycles, e.g. 100ms or 250us")
+                                    .short("i")
+                                    .long("interval")
+                                    .takes_value(true),
+                            )
+                            .arg(
+                                Arg::with_name("counter_byte")
+                                    .help("index of a data byte incremented with every cycle")
+                                    .long("counter-byte")
+                                    .takes_value(true),
+                            )
+                            .arg(
+                                Arg::with_name("timeout")
+                                    .help("time in ms to wait while the TX queue is full before failing, 0 waits forever (default 1000)")
+                                    .short("t")
+                                    .long("timeout")
+                                    .takes_value(true),
+                            )
+                            .arg(
+                                Arg::with_name("bcm")
+                                    .help("sends the frames cyclically by the kernel (broadcast manager), one job per frame ID")
+                                    .long("bcm")
+                                    .requires("interval")
+                                    .conflicts_with_all(&["stdin", "counter_byte", "timeout"]),
+                            )
+                            .arg(
+                                Arg::with_name("update")
+                                    .help("reads frames from stdin updating the data of the --bcm job with the same ID")
+                                    .long("update")
+                                    .requires("bcm"),
                             )
                             .get_matches();
 
@@ -172,6 +340,91 @@ fn main() {
             process::exit(1);
         },
     };
+    let frame_strings: Vec<&str> = arg_matches
+        .values_of("frame")
+        .map_or(Vec::new(), |values| values.collect());
+    if frame_strings.len() > 1 && frame_strings.contains(&"-") {
+        log::error!("Frames read from stdin ('-') cannot be mixed with frames given as arguments!");
+        process::exit(1);
+    }
+    let read_stdin: bool = arg_matches.is_present("stdin") || frame_strings == ["-"];
+    if read_stdin && ["count", "interval", "counter_byte"].iter().any(|name| arg_matches.is_present(name)) {
+        log::error!("Cyclic transmission is not supported for frames read from stdin!");
+        process::exit(1);
+    }
+
+    // parse all frames before sending, so a typo does not send only a part of them
+    let mut frames: Vec<AnyFrame> = Vec::new();
+    if !read_stdin {
+        for frame_string in frame_strings.iter() {
+            match parse_frame_string(frame_string) {
+                Ok(frame) => frames.push(frame),
+                Err(error) => {
+                    log::error!("Error parsing frame-string! Error: {}\n{}", error, error.annotate(frame_string));
+                    process::exit(1);
+                },
+            }
+        }
+    }
+    let interval: Option<Duration> = arg_matches.value_of("interval").map(|value| match parse_interval(value) {
+        Some(interval) => interval,
+        None => {
+            log::error!("No valid interval given! Expected e.g. 100ms or 250us");
+            process::exit(1);
+        },
+    });
+    let count: usize = match arg_matches.value_of("count") {
+        Some(value) => match value.parse() {
+            Ok(count) => count,
+            Err(_) => {
+                log::error!("No valid count given!");
+                process::exit(1);
+            },
+        },
+        None if interval.is_some() => 0,
+        None => 1,
+    };
+    let counter_byte: Option<usize> = arg_matches.value_of("counter_byte").map(|value| match value.parse() {
+        Ok(index) => index,
+        Err(_) => {
+            log::error!("No valid counter byte given!");
+            process::exit(1);
+        },
+    });
+    if let Some(index) = counter_byte {
+        if let Some(frame) = frames.iter().find(|frame| frame.can_id().is_rtr() || index >= frame.data().len()) {
+            log::error!("Frame {} has no data byte {}!", frame, index);
+            process::exit(1);
+        }
+    }
+    let timeout: Option<Duration> = match arg_matches.value_of("timeout") {
+        Some(value) => match value.parse() {
+            Ok(0) => None,
+            Ok(millis) => Some(Duration::from_millis(millis)),
+            Err(_) => {
+                log::error!("No valid timeout given!");
+                process::exit(1);
+            },
+        },
+        None => Some(DEFAULT_TIMEOUT),
+    };
+    if arg_matches.is_present("bcm") {
+        let count: u32 = match u32::try_from(count) {
+            Ok(count) => count,
+            Err(_) => {
+                log::error!("No valid count given!");
+                process::exit(1);
+            },
+        };
+        for (index, frame) in frames.iter().enumerate() {
+            if frames[..index].iter().any(|other| other.can_id() == frame.can_id() && other.is_fd() == frame.is_fd()) {
+                log::error!("Frame ID of {} is used twice, the broadcast manager identifies jobs by ID!", frame);
+                process::exit(1);
+            }
+        }
+        send_bcm(can_socket_name, &frames, count, interval.unwrap_or_default(), arg_matches.is_present("update"));
+        process::exit(0)
+    }
     let can_socket: CANSocket = match CANSocket::open(can_socket_name) {
         Ok(socket) => socket,
         Err(error) => {
@@ -180,27 +433,16 @@ fn main() {
             process::exit(1);
         }
     };
-    let frame_string: String = arg_matches
-        .value_of("frame")
-        .unwrap()
-        .to_owned();
-
-    let frame: CANFrame = match parse_frame_string(frame_string) {
-        Some(frame) => frame,
-        None => {
-            log::error!("Error parsing frame-string!");
-            process::exit(1);
-        },
-    };
-    // blocking write function
-    match can_socket.write_frame_insist(&frame) {
-        Ok(()) => {
-            // socket will be closed on deallocation so nothing to do here
-            process::exit(0)
-        }
-        Err(error) => {
-            log::error!("Error sending frame! Error: {}", error);
-            process::exit(1)
+    let mut sender: Sender = Sender::new(can_socket, timeout);
+    if read_stdin {
+        send_stdin_frames(&mut sender);
+    } else if count != 1 || interval.is_some() || counter_byte.is_some() {
+        send_cyclic(&mut sender, &frames, count, interval.unwrap_or_default(), counter_byte);
+    } else {
+        for frame in frames.iter() {
+            sender.send(frame);
         }
     }
+    // socket will be closed on deallocation so nothing to do here
+    process::exit(0)
 }
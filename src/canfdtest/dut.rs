@@ -0,0 +1,588 @@
+//! DUT (device under test) part of the echo test
+
+use super::pattern::Pattern;
+use super::stats::BusEventStats;
+use super::{FailureReason, DEFAULT_DUT_ID, DEFAULT_HOST_ID};
+use crate::error_frame::decode_error_frame;
+use crate::frame::AnyFrame;
+use crate::id::CanId;
+use crate::transport::CanTransport;
+#[cfg(test)]
+use crate::fd::{CanFdFrame, CANFD_BRS};
+#[cfg(test)]
+use crate::error_frame::BusEvent;
+#[cfg(test)]
+use crate::transport::LoopbackBus;
+#[cfg(test)]
+use socketcan::CANFrame;
+use socketcan::{CANSocket, ShouldRetry, ERR_MASK};
+use std::error;
+use std::fmt;
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Interval in which a DUT waiting for frames checks its stop flag
+const STOP_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// DUT answering frames of the host with incremented frames
+///
+/// Frames are exchanged via a [`CanTransport`], usually a `CANSocket`.
+pub struct Dut<T: CanTransport = CANSocket> {
+    socket: T,
+    config: DutConfig,
+    stop: Arc<AtomicBool>,
+}
+
+/// Settings of the DUT
+#[derive(Debug, Clone)]
+pub struct DutConfig {
+    /// receive (and answer) CAN FD frames besides classic CAN frames
+    pub fd: bool,
+    /// ID of the frames sent by the host
+    pub host_id: CanId,
+    /// ID of the responses of the DUT
+    pub dut_id: CanId,
+    /// count errors instead of stopping, frames with unexpected data are answered anyway
+    pub tolerant: bool,
+    /// time to wait before answering a frame, e.g. to emulate a slow ECU
+    pub reply_delay: Duration,
+    /// stop after the given time, `None` runs until an error occurs
+    pub duration: Option<Duration>,
+    /// fail with a timeout if no frame is received within the given time, `None` waits forever
+    pub idle_timeout: Option<Duration>,
+    /// data pattern of the frames sent by the host
+    pub pattern: Pattern,
+}
+
+impl Default for DutConfig {
+    fn default() -> DutConfig {
+        DutConfig {
+            fd: false,
+            host_id: CanId::from_raw(DEFAULT_HOST_ID),
+            dut_id: CanId::from_raw(DEFAULT_DUT_ID),
+            tolerant: false,
+            reply_delay: Duration::ZERO,
+            duration: None,
+            idle_timeout: None,
+            pattern: Pattern::Counter,
+        }
+    }
+}
+
+/// Summary of the frames handled by the DUT
+#[derive(Debug, Clone, Default)]
+pub struct DutSummary {
+    /// count of frames received from the bus
+    pub received: usize,
+    /// count of frames answered
+    pub answered: usize,
+    /// count of frames with unrelated IDs ignored, e.g. of other nodes on the bus
+    pub ignored: usize,
+    /// count of frames of the host with unexpected data (or ID)
+    pub mismatches: usize,
+    /// bus events reported by error frames
+    pub bus_events: BusEventStats,
+}
+
+impl fmt::Display for DutSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Received: {}, answered: {}, ignored: {}, mismatches: {}\n{}",
+            self.received, self.answered, self.ignored, self.mismatches, self.bus_events)
+    }
+}
+
+/// Stopped DUT, returned by [`Dut::run`]
+#[derive(Debug, Clone)]
+pub struct DutFailure {
+    /// reason the DUT stopped
+    pub reason: FailureReason,
+    /// frames handled up to the failure
+    pub summary: DutSummary,
+}
+
+impl fmt::Display for DutFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}\nFailure reason: {}", self.summary, self.reason)
+    }
+}
+
+impl error::Error for DutFailure {}
+
+/// Error raised by the DUT
+#[derive(Debug)]
+pub struct DutError {
+    details: String,
+    reason: Option<FailureReason>,
+}
+
+impl DutError {
+    fn new(msg: &str) -> DutError {
+        DutError{details: msg.to_string(), reason: None}
+    }
+
+    fn with_reason(msg: &str, reason: FailureReason) -> DutError {
+        DutError{details: msg.to_string(), reason: Some(reason)}
+    }
+
+    /// Reason of the error if it fails the echo test, `None` for setup errors
+    pub fn reason(&self) -> Option<FailureReason> {
+        self.reason
+    }
+}
+
+impl fmt::Display for DutError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.details)
+    }
+}
+
+impl error::Error for DutError {}
+
+/// Checks a frame received from the host for the test ID and the data pattern
+/// # Arguments
+/// * 'frame' - classic CAN or CAN FD frame received from the bus
+/// * 'host_id' - ID of the frames sent by the host, including the frame format
+/// * 'pattern' - data pattern of the frames sent by the host
+pub fn check_frame(frame: AnyFrame, host_id: CanId, pattern: Pattern) -> Result<(), DutError> {
+    if frame.can_id() != host_id {
+        Err(DutError::with_reason("Received message ID mismatch!", FailureReason::IdMismatch))
+    } else if !pattern.validate(frame.data()) {
+        log::debug!("Received data bytes: {:x?}", frame.data());
+        Err(DutError::with_reason("Received data byte mismatch!", FailureReason::DataMismatch))
+    } else {
+        Ok(())
+    }
+}
+
+impl Dut {
+    /// Opens the given CAN socket for the DUT
+    /// # Arguments
+    /// * 'socket_name' - name of CAN socket, e.g. `can0`
+    /// * 'config' - settings of the DUT
+    pub fn new(socket_name: &str, config: DutConfig) -> Result<Dut, DutError> {
+        let can: CANSocket = match CANSocket::open(socket_name) {
+            Ok(socket) => socket,
+            Err(_) => return Err(DutError::new("Could not open socket")),
+        };
+        Dut::with_transport(can, config)
+    }
+}
+
+impl<T: CanTransport> Dut<T> {
+    /// Creates a DUT on an already opened transport
+    ///
+    /// Enables CAN FD frames on the transport in CAN FD mode. Sets a read
+    /// timeout on the transport, so the stop flag is checked while waiting
+    /// for frames.
+    /// # Arguments
+    /// * 'transport' - transport to the host, e.g. a `LoopbackTransport`
+    /// * 'config' - settings of the DUT
+    pub fn with_transport(transport: T, config: DutConfig) -> Result<Dut<T>, DutError> {
+        let read_timeout: Duration = match config.idle_timeout {
+            Some(timeout) if timeout.is_zero() => return Err(DutError::new("Idle timeout must not be zero")),
+            Some(timeout) => timeout.min(STOP_POLL_INTERVAL),
+            None => STOP_POLL_INTERVAL,
+        };
+        if transport.set_read_timeout(Some(read_timeout)).is_err() {
+            return Err(DutError::new("Could not set read timeout"));
+        }
+        if config.fd && transport.set_fd_frames(true).is_err() {
+            return Err(DutError::new("Could not enable CAN FD frames"));
+        }
+        if config.host_id == config.dut_id {
+            return Err(DutError::new("Host and DUT ID must differ"));
+        }
+        if transport.set_error_filter(ERR_MASK).is_err() {
+            return Err(DutError::new("Could not enable error frames"));
+        }
+        Ok(Dut {
+            socket: transport,
+            config,
+            stop: Arc::new(AtomicBool::new(false)),
+        })
+    }
+
+    /// Flag to stop the DUT from another thread or a signal handler
+    pub fn stop_flag(&self) -> Arc<AtomicBool> {
+        self.stop.clone()
+    }
+
+    fn stop_requested(&self, start: Instant) -> bool {
+        self.stop.load(Ordering::SeqCst) || self.config.duration.is_some_and(|duration| start.elapsed() >= duration)
+    }
+
+    /// Answers received frames until an error occurs, the configured duration
+    /// elapsed or the stop flag is set
+    ///
+    /// Frames with unrelated IDs, e.g. of other nodes on a shared bus, are
+    /// ignored. In tolerant mode frames with unexpected data are counted and
+    /// answered anyway, so the host notices the error as well.
+    ///
+    /// Returns the reason of the (first) error together with the frames handled so far.
+    pub fn run(self) -> Result<DutSummary, DutFailure> {
+        let mut summary: DutSummary = DutSummary::default();
+        let start: Instant = Instant::now();
+        let mut first_error: Option<FailureReason> = None;
+        let mut last_frame: Instant = start;
+        let failure: Option<FailureReason> = loop {
+            if self.stop_requested(start) {
+                log::info!("Stopping DUT.");
+                break first_error;
+            }
+            let received_frame: AnyFrame = match self.socket.read_frame() {
+                Ok(frame) => {
+                    log::debug!{"Received frame: {}", &frame};
+                    last_frame = Instant::now();
+                    frame
+                },
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(ref e) if e.should_retry() || e.kind() == io::ErrorKind::TimedOut => {
+                    if self.config.idle_timeout.is_some_and(|timeout| last_frame.elapsed() >= timeout) {
+                        log::error!("No frame received within {:?}!", self.config.idle_timeout.unwrap_or_default());
+                        break Some(FailureReason::Timeout);
+                    }
+                    continue;
+                },
+                Err(e) => {
+                    log::error!("Error receiving frame: {}", e);
+                    break Some(FailureReason::Receive);
+                },
+            };
+            if received_frame.can_id().is_error() {
+                let timestamp: Duration = start.elapsed();
+                for event in decode_error_frame(&received_frame) {
+                    log::warn!("Bus event: {} at {:.3} s", event, timestamp.as_secs_f64());
+                    summary.bus_events.add(event, timestamp);
+                }
+                continue;
+            }
+            summary.received += 1;
+            let check_result: Result<(), FailureReason> = check_frame(received_frame, self.config.host_id, self.config.pattern)
+                .map_err(|e| e.reason().unwrap_or(FailureReason::DataMismatch));
+            if let Err(reason) = check_result {
+                if reason == FailureReason::IdMismatch {
+                    log::debug!("Ignored frame {} with unrelated ID.", received_frame);
+                    summary.ignored += 1;
+                    continue;
+                }
+                if !self.config.tolerant {
+                    log::error!("Frame check did not pass! Frame: {}", received_frame);
+                    summary.mismatches += 1;
+                    break Some(reason);
+                }
+                log::warn!("Frame check did not pass! Frame: {}", received_frame);
+                summary.mismatches += 1;
+                first_error.get_or_insert(reason);
+            }
+            let frame: AnyFrame = match super::response_frame(received_frame, self.config.dut_id) {
+                None => {
+                    log::error!("Error incrementing frame for sending!");
+                    break Some(FailureReason::Send);
+                },
+                Some(f) => f,
+            };
+            if !self.config.reply_delay.is_zero() {
+                thread::sleep(self.config.reply_delay);
+            }
+            match self.socket.write_frame(&frame) {
+                Ok(_) => summary.answered += 1,
+                Err(e) => {
+                    log::error!("Error while writing frame! {}", e);
+                    break Some(FailureReason::Send);
+                },
+            }
+        };
+        log::info!("Received {} frames.", summary.received);
+        match failure {
+            None => Ok(summary),
+            Some(reason) => Err(DutFailure { reason, summary }),
+        }
+    }
+}
+
+#[test]
+fn test_correct_frame_check() {
+    let correct_frame: CANFrame = CANFrame::new(0x77, &[1, 2, 3], false, false)
+        .unwrap();
+    assert!(check_frame(correct_frame.into(), CanId::from_raw(DEFAULT_HOST_ID), Pattern::Counter)
+        .is_ok()
+    );
+}
+
+#[test]
+fn test_false_id_frame_check() {
+    let false_id_frame: CANFrame = CANFrame::new(0x123, &[1, 2, 3], false, false)
+        .unwrap();
+    assert_eq!(Some(FailureReason::IdMismatch), check_frame(false_id_frame.into(), CanId::from_raw(DEFAULT_HOST_ID), Pattern::Counter)
+        .unwrap_err()
+        .reason()
+    );
+}
+
+#[test]
+fn test_false_data_frame_check() {
+    let false_data_frame: CANFrame = CANFrame::new(0x77, &[1, 1, 3], false, false)
+        .unwrap();
+    assert_eq!(Some(FailureReason::DataMismatch), check_frame(false_data_frame.into(), CanId::from_raw(DEFAULT_HOST_ID), Pattern::Counter)
+        .unwrap_err()
+        .reason()
+    );
+}
+
+#[test]
+fn test_dut_run_loopback() {
+    let bus: LoopbackBus = LoopbackBus::new();
+    let host = bus.connect();
+    let dut_transport = bus.connect();
+    // DUT stops after not receiving any frame for a while
+    let config: DutConfig = DutConfig {
+        idle_timeout: Some(Duration::from_millis(100)),
+        ..DutConfig::default()
+    };
+    let dut: Dut<_> = Dut::with_transport(dut_transport, config)
+        .unwrap();
+    let dut_thread = thread::spawn(move || dut.run());
+    for counter in 0..10 {
+        let data_bytes: Vec<u8> = (0..8).map(|i| counter + i).collect();
+        let frame: AnyFrame = CANFrame::new(0x77, &data_bytes, false, false)
+            .unwrap()
+            .into();
+        host.write_frame(&frame)
+            .unwrap();
+        let response: AnyFrame = host.read_frame()
+            .unwrap();
+        assert_eq!(0x78, response.id());
+        assert_eq!(super::increment_frame(frame).unwrap().data(), response.data());
+    }
+    let failure: DutFailure = dut_thread.join()
+        .unwrap()
+        .unwrap_err();
+    assert_eq!(FailureReason::Timeout, failure.reason);
+}
+
+#[test]
+fn test_dut_run_fd_loopback() {
+    let bus: LoopbackBus = LoopbackBus::new();
+    let host = bus.connect();
+    host.set_fd_frames(true)
+        .unwrap();
+    let dut_transport = bus.connect();
+    let config: DutConfig = DutConfig {
+        idle_timeout: Some(Duration::from_millis(100)),
+        fd: true,
+        ..DutConfig::default()
+    };
+    let dut: Dut<_> = Dut::with_transport(dut_transport, config)
+        .unwrap();
+    let dut_thread = thread::spawn(move || dut.run());
+    let data_bytes: Vec<u8> = (0..64).collect();
+    let frame: AnyFrame = CanFdFrame::new(0x77, &data_bytes, CANFD_BRS)
+        .unwrap()
+        .into();
+    host.write_frame(&frame)
+        .unwrap();
+    let response: CanFdFrame = host.read_frame()
+        .unwrap()
+        .fd()
+        .unwrap();
+    assert_eq!(0x78, response.id());
+    assert_eq!(64, response.data().len());
+    assert!(response.is_brs());
+    let failure: DutFailure = dut_thread.join()
+        .unwrap()
+        .unwrap_err();
+    assert_eq!(FailureReason::Timeout, failure.reason);
+}
+
+#[test]
+fn test_extended_id_frame_check() {
+    let frame: AnyFrame = CANFrame::new(0x77, &[1, 2, 3], false, false)
+        .unwrap()
+        .into();
+    // same ID value in the other frame format is rejected
+    assert!(check_frame(frame, CanId::extended(0x77).unwrap(), Pattern::Counter)
+        .is_err()
+    );
+}
+
+#[test]
+fn test_dut_run_data_mismatch() {
+    let bus: LoopbackBus = LoopbackBus::new();
+    let host = bus.connect();
+    let dut: Dut<_> = Dut::with_transport(bus.connect(), DutConfig::default())
+        .unwrap();
+    let dut_thread = thread::spawn(move || dut.run());
+    let frame: AnyFrame = CANFrame::new(0x77, &[1, 1, 3], false, false)
+        .unwrap()
+        .into();
+    host.write_frame(&frame)
+        .unwrap();
+    let failure: DutFailure = dut_thread.join()
+        .unwrap()
+        .unwrap_err();
+    assert_eq!(FailureReason::DataMismatch, failure.reason);
+    assert_eq!(0, failure.summary.answered);
+}
+
+#[test]
+fn test_dut_run_foreign_id() {
+    let bus: LoopbackBus = LoopbackBus::new();
+    let host = bus.connect();
+    let other_node = bus.connect();
+    let dut_transport = bus.connect();
+    let config: DutConfig = DutConfig {
+        idle_timeout: Some(Duration::from_millis(100)),
+        ..DutConfig::default()
+    };
+    let dut: Dut<_> = Dut::with_transport(dut_transport, config)
+        .unwrap();
+    let dut_thread = thread::spawn(move || dut.run());
+    // frame of another node on the bus does not stop the test
+    other_node.write_frame(&CANFrame::new(0x123, &[1, 1, 3], false, false).unwrap().into())
+        .unwrap();
+    host.write_frame(&CANFrame::new(0x77, &[1, 2, 3], false, false).unwrap().into())
+        .unwrap();
+    // the host receives the frame of the other node as well
+    assert_eq!(0x123, host.read_frame().unwrap().id());
+    assert_eq!(&[2, 3, 4], host.read_frame().unwrap().data());
+    let failure: DutFailure = dut_thread.join()
+        .unwrap()
+        .unwrap_err();
+    assert_eq!(FailureReason::Timeout, failure.reason);
+    assert_eq!(1, failure.summary.answered);
+    assert_eq!(1, failure.summary.ignored);
+    assert_eq!(0, failure.summary.mismatches);
+}
+
+#[test]
+fn test_dut_run_tolerant() {
+    let bus: LoopbackBus = LoopbackBus::new();
+    let host = bus.connect();
+    let dut_transport = bus.connect();
+    let config: DutConfig = DutConfig {
+        idle_timeout: Some(Duration::from_millis(100)),
+        tolerant: true,
+        ..DutConfig::default()
+    };
+    let dut: Dut<_> = Dut::with_transport(dut_transport, config)
+        .unwrap();
+    let dut_thread = thread::spawn(move || dut.run());
+    let frames: [CANFrame; 3] = [
+        CANFrame::new(0x123, &[1, 2, 3], false, false).unwrap(),
+        CANFrame::new(0x77, &[1, 1, 3], false, false).unwrap(),
+        CANFrame::new(0x77, &[1, 2, 3], false, false).unwrap(),
+    ];
+    for frame in frames.iter() {
+        host.write_frame(&(*frame).into())
+            .unwrap();
+    }
+    // frame with unexpected data is answered anyway
+    assert_eq!(&[2, 2, 4], host.read_frame().unwrap().data());
+    assert_eq!(&[2, 3, 4], host.read_frame().unwrap().data());
+    let failure: DutFailure = dut_thread.join()
+        .unwrap()
+        .unwrap_err();
+    assert_eq!(FailureReason::Timeout, failure.reason);
+    assert_eq!(3, failure.summary.received);
+    assert_eq!(2, failure.summary.answered);
+    assert_eq!(1, failure.summary.ignored);
+    assert_eq!(1, failure.summary.mismatches);
+}
+
+#[test]
+fn test_dut_run_reply_delay() {
+    let bus: LoopbackBus = LoopbackBus::new();
+    let host = bus.connect();
+    let dut_transport = bus.connect();
+    let config: DutConfig = DutConfig {
+        idle_timeout: Some(Duration::from_millis(100)),
+        reply_delay: Duration::from_millis(20),
+        ..DutConfig::default()
+    };
+    let dut: Dut<_> = Dut::with_transport(dut_transport, config)
+        .unwrap();
+    let dut_thread = thread::spawn(move || dut.run());
+    let sent: Instant = Instant::now();
+    host.write_frame(&CANFrame::new(0x77, &[1, 2, 3], false, false).unwrap().into())
+        .unwrap();
+    assert_eq!(&[2, 3, 4], host.read_frame().unwrap().data());
+    assert!(sent.elapsed() >= Duration::from_millis(20));
+    let failure: DutFailure = dut_thread.join()
+        .unwrap()
+        .unwrap_err();
+    assert_eq!(1, failure.summary.answered);
+}
+
+#[test]
+fn test_dut_run_duration() {
+    let bus: LoopbackBus = LoopbackBus::new();
+    let host = bus.connect();
+    let config: DutConfig = DutConfig {
+        duration: Some(Duration::from_millis(50)),
+        ..DutConfig::default()
+    };
+    let dut: Dut<_> = Dut::with_transport(bus.connect(), config)
+        .unwrap();
+    let dut_thread = thread::spawn(move || dut.run());
+    host.write_frame(&CANFrame::new(0x77, &[1, 2, 3], false, false).unwrap().into())
+        .unwrap();
+    assert_eq!(&[2, 3, 4], host.read_frame().unwrap().data());
+    let summary: DutSummary = dut_thread.join()
+        .unwrap()
+        .unwrap();
+    assert_eq!(1, summary.answered);
+}
+
+#[test]
+fn test_dut_run_stop_flag() {
+    let bus: LoopbackBus = LoopbackBus::new();
+    let dut: Dut<_> = Dut::with_transport(bus.connect(), DutConfig::default())
+        .unwrap();
+    dut.stop_flag().store(true, Ordering::SeqCst);
+    let summary: DutSummary = dut.run()
+        .unwrap();
+    assert_eq!(0, summary.received);
+}
+
+#[test]
+fn test_dut_run_bus_events() {
+    let bus: LoopbackBus = LoopbackBus::new();
+    let driver = bus.connect();
+    let dut_transport = bus.connect();
+    let config: DutConfig = DutConfig {
+        idle_timeout: Some(Duration::from_millis(100)),
+        ..DutConfig::default()
+    };
+    let dut: Dut<_> = Dut::with_transport(dut_transport, config)
+        .unwrap();
+    let dut_thread = thread::spawn(move || dut.run());
+    // bus-off followed by a restart of the controller
+    for class in [0x40, 0x100].iter() {
+        driver.write_frame(&CANFrame::new(*class, &[], false, true).unwrap().into())
+            .unwrap();
+    }
+    let failure: DutFailure = dut_thread.join()
+        .unwrap()
+        .unwrap_err();
+    assert_eq!(FailureReason::Timeout, failure.reason);
+    assert_eq!(0, failure.summary.received);
+    assert_eq!(1, failure.summary.bus_events.get(BusEvent::BusOff).unwrap().count);
+    assert_eq!(1, failure.summary.bus_events.get(BusEvent::Restarted).unwrap().count);
+}
+
+#[test]
+fn test_pattern_frame_check() {
+    let host_id: CanId = CanId::from_raw(DEFAULT_HOST_ID);
+    let zeros: CANFrame = CANFrame::new(DEFAULT_HOST_ID, &[0; 8], false, false)
+        .unwrap();
+    assert!(check_frame(zeros.into(), host_id, Pattern::Zeros).is_ok());
+    assert_eq!(Some(FailureReason::DataMismatch), check_frame(zeros.into(), host_id, Pattern::Ones)
+        .unwrap_err()
+        .reason());
+    let empty: CANFrame = CANFrame::new(DEFAULT_HOST_ID, &[], false, false)
+        .unwrap();
+    assert!(check_frame(empty.into(), host_id, Pattern::Random).is_ok());
+}
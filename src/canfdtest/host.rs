@@ -0,0 +1,1331 @@
+//! Host (generator) part of the echo test
+
+use super::pattern::{Pattern, PatternGenerator};
+use super::stats::{BusEventStats, LatencyStats};
+use super::{FailureReason, DEFAULT_DUT_ID, DEFAULT_HOST_ID, DEFAULT_INFLIGHT_COUNT};
+use crate::error_frame::decode_error_frame;
+use crate::fd::{CanFdFrame, CANFD_BRS};
+#[cfg(test)]
+use crate::fd::CANFD_VALID_LENGTHS;
+use crate::frame::{new_can_frame, AnyFrame};
+use crate::id::CanId;
+use crate::transport::CanTransport;
+#[cfg(test)]
+use crate::error_frame::BusEvent;
+#[cfg(test)]
+use crate::transport::LoopbackBus;
+#[cfg(test)]
+use socketcan::CANFrame;
+#[cfg(test)]
+use std::sync::Mutex;
+use socketcan::{CANSocket, ShouldRetry, ERR_MASK};
+use std::collections::VecDeque;
+use std::error;
+use std::fmt;
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use std::thread;
+
+/// Time to wait for the response to a frame before it is considered lost
+pub const DEFAULT_RESPONSE_TIMEOUT: Duration = Duration::from_millis(1000);
+
+/// Time to wait after sending a frame
+pub const DEFAULT_FRAME_GAP: Duration = Duration::from_millis(1);
+
+/// Count of frames sent before waiting the burst gap instead of the frame gap
+pub const DEFAULT_BURST_LENGTH: usize = 33;
+
+/// Time to wait after sending a burst of frames
+pub const DEFAULT_BURST_GAP: Duration = Duration::from_millis(3);
+
+/// Error raised by the host
+#[derive(Debug)]
+pub struct HostError {
+    details: String,
+    reason: Option<FailureReason>,
+}
+
+impl HostError {
+    fn new(msg: &str) -> HostError {
+        HostError{details: msg.to_string(), reason: None}
+    }
+
+    fn with_reason(msg: &str, reason: FailureReason) -> HostError {
+        HostError{details: msg.to_string(), reason: Some(reason)}
+    }
+
+    /// Reason of the error if it fails the echo test, `None` for setup errors
+    pub fn reason(&self) -> Option<FailureReason> {
+        self.reason
+    }
+}
+
+/// Failed echo test, returned by [`Host::run`]
+#[derive(Debug, Clone)]
+pub struct HostFailure {
+    /// reason the test stopped
+    pub reason: FailureReason,
+    /// statistics up to the failure
+    pub summary: Box<HostSummary>,
+}
+
+impl fmt::Display for HostFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}\nFailure reason: {}", self.summary, self.reason)
+    }
+}
+
+impl error::Error for HostFailure {}
+
+/// Frame received by the host not matching the expected frame
+#[derive(Debug, Copy, Clone)]
+pub struct FrameMismatch {
+    /// sequence number of the test frame
+    pub sequence: usize,
+    /// expected frame, i.e. the own frame or the response of the DUT
+    pub expected: AnyFrame,
+    /// frame received from the bus
+    pub received: AnyFrame,
+}
+
+/// Progress of a running test, reported in the configured interval
+///
+/// Rates are calculated since the previous report.
+#[derive(Debug, Clone)]
+pub struct HostProgress {
+    /// test frames sent so far
+    pub sent: usize,
+    /// responses received so far
+    pub received: usize,
+    /// received responses per second
+    pub frames_per_second: f64,
+    /// payload throughput of the responses in kbit/s
+    pub throughput_kbit: f64,
+    /// frames sent but not yet answered
+    pub inflight: usize,
+}
+
+impl fmt::Display for HostProgress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Sent: {}, received: {}, {:.1} frames/s, payload throughput: {:.1} kbit/s, in flight: {}",
+            self.sent, self.received, self.frames_per_second, self.throughput_kbit, self.inflight)
+    }
+}
+
+/// Handler of the progress reports of a running test
+type ProgressHandler = Box<dyn FnMut(&HostProgress) + Send>;
+
+/// Summary of an echo test run by the host
+#[derive(Debug, Clone, Default)]
+pub struct HostSummary {
+    /// count of frames sent to the DUT
+    pub sent: usize,
+    /// count of frames received from the DUT
+    pub received: usize,
+    /// count of frames received from the DUT (or own frames) not matching the sent frame
+    pub mismatches: usize,
+    /// count of frames not answered within the response timeout
+    pub lost_frames: usize,
+    /// count of frames received in an unexpected order
+    pub sequence_errors: usize,
+    /// count of frames with unrelated IDs ignored, e.g. of other nodes on the bus
+    pub ignored: usize,
+    /// `true` if all test frames were answered by the DUT
+    pub completed: bool,
+    /// sequence number of the first frame not answered in time
+    pub first_lost: Option<usize>,
+    /// duration of the test
+    pub elapsed: Duration,
+    /// count of data bytes of the frames sent to and received from the DUT
+    pub payload_bytes: usize,
+    /// round trip latencies of the frames answered by the DUT
+    pub latency: LatencyStats,
+    /// first received frame not matching the expected frame
+    pub first_mismatch: Option<FrameMismatch>,
+    /// bus events reported by error frames
+    pub bus_events: BusEventStats,
+}
+
+impl HostSummary {
+    /// Check if all test frames were answered correctly
+    pub fn passed(&self) -> bool {
+        self.completed && self.errors() == 0
+    }
+
+    /// Total count of mismatches, lost frames and sequence errors
+    pub fn errors(&self) -> usize {
+        self.mismatches + self.lost_frames + self.sequence_errors
+    }
+
+    fn count_error(&mut self, reason: FailureReason) {
+        match reason {
+            FailureReason::IdMismatch | FailureReason::DataMismatch => self.mismatches += 1,
+            FailureReason::Timeout => self.lost_frames += 1,
+            FailureReason::Sequence => self.sequence_errors += 1,
+            FailureReason::Send | FailureReason::Receive => {},
+        }
+    }
+
+    /// Frames answered by the DUT per second
+    pub fn frame_rate(&self) -> f64 {
+        rate(self.received, self.elapsed)
+    }
+
+    /// Payload throughput of host and DUT frames in kbit/s
+    pub fn throughput(&self) -> f64 {
+        rate(self.payload_bytes * 8, self.elapsed) / 1000.0
+    }
+}
+
+fn rate(count: usize, elapsed: Duration) -> f64 {
+    if elapsed.is_zero() {
+        return 0.0;
+    }
+    count as f64 / elapsed.as_secs_f64()
+}
+
+impl fmt::Display for HostSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Test {}! Sent: {}, received: {}, mismatches: {}, lost: {}, sequence errors: {}, ignored: {}, elapsed time: {:.3} s",
+            if self.passed() { "passed" } else { "failed" },
+            self.sent, self.received, self.mismatches, self.lost_frames, self.sequence_errors,
+            self.ignored, self.elapsed.as_secs_f64())?;
+        if let Some(sequence) = self.first_lost {
+            write!(f, ", first lost frame: {}", sequence)?;
+        }
+        write!(f, "\n{:.1} frames/s, payload throughput: {:.1} kbit/s\n{}\n{}",
+            self.frame_rate(), self.throughput(), self.latency, self.bus_events)
+    }
+}
+
+/// Settings of the host
+#[derive(Debug, Clone)]
+pub struct HostConfig {
+    /// count of frames sent before waiting for responses of the DUT
+    pub inflight_count: usize,
+    /// count of test frames, `0` runs until an error occurs
+    pub frame_count: usize,
+    /// time to wait for the response to a frame before it is considered lost
+    pub response_timeout: Duration,
+    /// send CAN FD frames cycling through all valid CAN FD data lengths
+    pub fd: bool,
+    /// set the bit rate switch flag on CAN FD frames
+    pub brs: bool,
+    /// ID of the frames sent by the host
+    pub host_id: CanId,
+    /// ID of the responses of the DUT
+    pub dut_id: CanId,
+    /// interval of progress reports, `None` disables them
+    pub progress_interval: Option<Duration>,
+    /// count errors and resynchronize instead of stopping at the first error
+    pub tolerant: bool,
+    /// time to wait after sending a frame, zero sends at full bus load
+    pub frame_gap: Duration,
+    /// count of frames sent before waiting `burst_gap` instead of `frame_gap`, `0` disables bursts
+    pub burst_length: usize,
+    /// time to wait after sending a burst of frames
+    pub burst_gap: Duration,
+    /// stop sending test frames after the given time, `None` runs until `frame_count` is reached
+    pub duration: Option<Duration>,
+    /// data pattern of the test frames, the DUT has to check the same pattern
+    pub pattern: Pattern,
+    /// seed of random data and random lengths, the same seed reproduces the same frames
+    pub seed: u64,
+    /// use random data lengths instead of the longest (classic CAN) or
+    /// cycling through all valid lengths (CAN FD)
+    pub random_length: bool,
+}
+
+impl Default for HostConfig {
+    fn default() -> HostConfig {
+        HostConfig {
+            inflight_count: DEFAULT_INFLIGHT_COUNT,
+            frame_count: 0,
+            response_timeout: DEFAULT_RESPONSE_TIMEOUT,
+            fd: false,
+            brs: false,
+            host_id: CanId::from_raw(DEFAULT_HOST_ID),
+            dut_id: CanId::from_raw(DEFAULT_DUT_ID),
+            progress_interval: None,
+            tolerant: false,
+            frame_gap: DEFAULT_FRAME_GAP,
+            burst_length: DEFAULT_BURST_LENGTH,
+            burst_gap: DEFAULT_BURST_GAP,
+            duration: None,
+            pattern: Pattern::Counter,
+            seed: 0,
+            random_length: false,
+        }
+    }
+}
+
+/// Host sending frames to the DUT and checking its responses
+///
+/// Frames are exchanged via a [`CanTransport`], usually a `CANSocket`.
+pub struct Host<T: CanTransport = CANSocket> {
+    socket: T,
+    config: HostConfig,
+    stop: Arc<AtomicBool>,
+    progress_handler: Option<ProgressHandler>,
+}
+
+impl Host {
+
+    /// Compares a received frame against the expected one
+    /// # Arguments
+    /// * 'expected_frame' - frame sent by the host
+    /// * 'received_frame' - frame received from the bus
+    /// * 'increment' - `0` compares both frames as they are, otherwise the
+    ///   expected frame is incremented first (i.e. the response of the DUT)
+    ///
+    /// Returns an error on mismatch if `increment` is `0`, otherwise `Ok(false)`.
+    pub fn compare_frame(expected_frame: AnyFrame, received_frame: AnyFrame, increment: usize) -> Result<bool, HostError> {
+        if increment == 0 {
+            match (expected_frame, received_frame) {
+                (AnyFrame::Classic(_), AnyFrame::Classic(_)) => {},
+                (AnyFrame::Fd(expected), AnyFrame::Fd(received)) => {
+                    if expected.flags() != received.flags() {
+                        log::error!("Expected CAN FD flags: {:X}, Received CAN FD flags: {:X}", expected.flags(), received.flags());
+                        return Err(HostError::with_reason("CAN FD flags mismatch!", FailureReason::DataMismatch));
+                    }
+                },
+                _ => {
+                    log::error!("Expected frame: {}, Received frame: {}", expected_frame, received_frame);
+                    return Err(HostError::with_reason("Frame type mismatch!", FailureReason::DataMismatch));
+                },
+            }
+            if expected_frame.id() != received_frame.id() {
+                log::error!("Expected ID: {}, Received ID: {}", expected_frame.id(), received_frame.id());
+                return Err(HostError::with_reason("ID mismatch!", FailureReason::IdMismatch));
+            } else if expected_frame.data() != received_frame.data() {
+                log::error!("Expected data: {:x?}, Received data: {:x?}", expected_frame.data(), received_frame.data());
+                return Err(HostError::with_reason("Data byte mismatch!", FailureReason::DataMismatch));
+            }
+            Ok(true)
+         } else {
+             let new_expected_frame: AnyFrame = match super::increment_frame(expected_frame) {
+                 Some(f) => f,
+                 None => return Err(HostError::new("Could not compare expteded receive from DUT!")),
+             };
+             if Host::compare_frame(new_expected_frame, received_frame, 0).is_err() {
+                 Ok(false)
+             } else {
+                 Ok(true)
+             }
+         }
+    }
+
+    /// Opens the given CAN socket for the host
+    /// # Arguments
+    /// * 'socket' - name of CAN socket, e.g. `can0`
+    /// * 'config' - settings of the test
+    pub fn new(socket: &str, config: HostConfig) -> Result<Host, HostError> {
+
+        let can: CANSocket = match CANSocket::open(socket) {
+            Ok(socket) => socket,
+            Err(_) => return Err(HostError::new("Error opening socket!")),
+        };
+        Host::with_transport(can, config)
+    }
+}
+
+impl<T: CanTransport> Host<T> {
+    /// Creates a host on an already opened transport
+    ///
+    /// Enables receiving own frames (and CAN FD frames in CAN FD mode) on the transport.
+    /// # Arguments
+    /// * 'transport' - transport to the DUT, e.g. a `LoopbackTransport`
+    /// * 'config' - settings of the test
+    pub fn with_transport(transport: T, config: HostConfig) -> Result<Host<T>, HostError> {
+        if config.inflight_count == 0 {
+            return Err(HostError::new("Inflight count must be at least 1!"));
+        }
+        if config.host_id == config.dut_id {
+            return Err(HostError::new("Host and DUT ID must differ!"));
+        }
+        if config.brs && !config.fd {
+            return Err(HostError::new("Bit rate switch requires CAN FD mode!"));
+        }
+        if config.fd && transport.set_fd_frames(true).is_err() {
+            return Err(HostError::new("Could not enable CAN FD frames!"));
+        }
+        if transport.set_recv_own_msgs(true).is_err() {
+            return Err(HostError::new("Could not enable receiving own frames!"));
+        }
+        if transport.set_read_timeout(Some(config.response_timeout)).is_err() {
+            return Err(HostError::new("Could not set response timeout!"));
+        }
+        if transport.set_error_filter(ERR_MASK).is_err() {
+            return Err(HostError::new("Could not enable error frames!"));
+        }
+        Ok(Host {
+            socket: transport,
+            config,
+            stop: Arc::new(AtomicBool::new(false)),
+            progress_handler: None,
+        })
+    }
+
+    /// Sets the handler called with the progress in the configured interval
+    ///
+    /// Without a handler the progress is logged.
+    /// # Arguments
+    /// * 'handler' - called with every progress report, e.g. to print it
+    pub fn on_progress<F: FnMut(&HostProgress) + Send + 'static>(&mut self, handler: F) {
+        self.progress_handler = Some(Box::new(handler));
+    }
+
+    /// Flag to stop the test from another thread or a signal handler
+    ///
+    /// After setting it no further test frames are sent, the frames still in
+    /// flight are awaited and the test ends like a completed run.
+    pub fn stop_flag(&self) -> Arc<AtomicBool> {
+        self.stop.clone()
+    }
+
+    fn stop_requested(&self, start: Instant) -> bool {
+        self.stop.load(Ordering::SeqCst) || self.config.duration.is_some_and(|duration| start.elapsed() >= duration)
+    }
+
+    /// Runs the echo test until all test frames are answered or an error occurs
+    ///
+    /// Every frame sent has to be received as own frame before the response
+    /// of the DUT is received. After sending the last test frame the frames
+    /// still in flight are awaited. The test stops if a frame is not answered
+    /// within the response timeout.
+    ///
+    /// Frames with IDs other than the host and DUT ID, e.g. of other nodes on a
+    /// shared bus, are ignored. In tolerant mode errors are counted instead:
+    /// lost frames are dropped and responses are matched to the frame they
+    /// belong to.
+    ///
+    /// After the configured duration or when the stop flag is set, the frames
+    /// still in flight are awaited before the test ends.
+    ///
+    /// Reports the progress of the test in the configured interval, see [`Host::on_progress`].
+    ///
+    /// Returns the reason of the (first) failure together with the statistics
+    /// if the test does not pass.
+    pub fn run(mut self) -> Result<HostSummary, HostFailure> {
+        let mut generator: PatternGenerator = PatternGenerator::new(self.config.pattern, self.config.seed,
+            self.config.random_length, self.config.fd);
+        let mut tx_frames: InflightFrames = InflightFrames::new(self.config.inflight_count, self.config.dut_id, self.config.tolerant);
+        let mut summary: HostSummary = HostSummary::default();
+        let start: Instant = Instant::now();
+        let mut progress: Progress = Progress::new(start);
+        let mut first_error: Option<FailureReason> = None;
+        let mut stopping: bool = false;
+
+        let failure: Option<FailureReason> = loop {
+            if let Some(interval) = self.config.progress_interval {
+                if progress.due(interval) {
+                    let report: HostProgress = progress.update(&summary, tx_frames.len());
+                    match self.progress_handler.as_mut() {
+                        Some(handler) => handler(&report),
+                        None => log::info!("{}", report),
+                    }
+                }
+            }
+            if !stopping && self.stop_requested(start) {
+                log::info!("Stopping test, waiting for {} frames in flight.", tx_frames.len());
+                stopping = true;
+            }
+            if stopping && tx_frames.len() == 0 {
+                summary.completed = true;
+                break first_error;
+            }
+            if self.config.frame_count != 0 && summary.received + summary.lost_frames >= self.config.frame_count {
+                summary.completed = true;
+                break first_error;
+            }
+            let sending_done: bool = stopping || (self.config.frame_count != 0 && summary.sent == self.config.frame_count);
+            if tx_frames.len() < self.config.inflight_count && !sending_done {
+                let frame: AnyFrame = match self.test_frame(&generator.next_data(summary.sent)) {
+                    Some(f) => f,
+                    None => {
+                        log::error!("Could not create frame for sending! At index {}", tx_frames.len());
+                        break Some(FailureReason::Send);
+                    },
+                };
+                match self.socket.write_frame(&frame) {
+                    Ok(_) => {
+                        tx_frames.push(frame);
+                        summary.sent += 1;
+                        summary.payload_bytes += frame.data().len();
+                    },
+                    Err(_) => {
+                        log::error!("Could not send frame! Frame: {} at index {}", frame, tx_frames.len());
+                        break Some(FailureReason::Send);
+                    },
+                }
+                let gap: Duration = self.gap(summary.sent);
+                if !gap.is_zero() {
+                    thread::sleep(gap);
+                }
+            } else {
+                let received_frame: Option<AnyFrame> = match self.socket.read_frame() {
+                    Ok(frame) => {
+                        log::debug!{"Received frame: {:x?}", &frame};
+                        Some(frame)
+                    },
+                    Err(ref e) if e.should_retry() || e.kind() == io::ErrorKind::Interrupted => None,
+                    Err(e) => {
+                        log::error!("Error receiving frame: {}", e);
+                        break Some(FailureReason::Receive);
+                    },
+                };
+                if let Some(sequence) = tx_frames.expired(self.config.response_timeout) {
+                    summary.first_lost.get_or_insert(sequence);
+                    summary.count_error(FailureReason::Timeout);
+                    if !self.config.tolerant {
+                        break Some(FailureReason::Timeout);
+                    }
+                    tx_frames.drop_front();
+                    first_error.get_or_insert(FailureReason::Timeout);
+                }
+                let received_frame: AnyFrame = match received_frame {
+                    Some(frame) => frame,
+                    None => continue,
+                };
+                if received_frame.can_id().is_error() {
+                    let timestamp: Duration = start.elapsed();
+                    for event in decode_error_frame(&received_frame) {
+                        log::warn!("Bus event: {} at {:.3} s", event, timestamp.as_secs_f64());
+                        summary.bus_events.add(event, timestamp);
+                    }
+                    continue;
+                }
+
+                let result: Result<(), FailureReason> = if received_frame.can_id() == self.config.host_id {
+                    log::debug!("Received own frame.");
+                    match tx_frames.own_frame(received_frame) {
+                        Ok(true) => Ok(()),
+                        Ok(false) => Err(FailureReason::Sequence),
+                        Err(reason) => Err(reason),
+                    }
+                } else if received_frame.can_id() != self.config.dut_id {
+                    log::debug!("Ignored frame {} with unrelated ID.", received_frame);
+                    summary.ignored += 1;
+                    continue;
+                } else {
+                    log::debug!("Received DUT frame.");
+                    match tx_frames.dut_frame(received_frame) {
+                        Ok(response) => {
+                            summary.received += 1;
+                            summary.payload_bytes += received_frame.data().len();
+                            summary.latency.add(response.latency);
+                            log::debug!("Frame comparison passed.");
+                            if response.in_order { Ok(()) } else { Err(FailureReason::Sequence) }
+                        },
+                        Err(reason @ FailureReason::IdMismatch) | Err(reason @ FailureReason::DataMismatch) => {
+                            summary.received += 1;
+                            summary.payload_bytes += received_frame.data().len();
+                            log::error!("Frame comparison failed!");
+                            Err(reason)
+                        },
+                        Err(reason) => Err(reason),
+                    }
+                };
+                if let Err(reason) = result {
+                    summary.count_error(reason);
+                    if !self.config.tolerant {
+                        break Some(reason);
+                    }
+                    first_error.get_or_insert(reason);
+                }
+            }
+        };
+        summary.elapsed = start.elapsed();
+        summary.first_mismatch = tx_frames.first_mismatch;
+        match failure {
+            None => Ok(summary),
+            Some(reason) => Err(HostFailure { reason, summary: Box::new(summary) }),
+        }
+    }
+
+    /// Time to wait after sending the given count of frames
+    fn gap(&self, sent: usize) -> Duration {
+        if self.config.burst_length != 0 && sent % self.config.burst_length == 0 {
+            self.config.burst_gap
+        } else {
+            self.config.frame_gap
+        }
+    }
+
+    /// Creates a test frame with the given data bytes
+    fn test_frame(&self, data_bytes: &[u8]) -> Option<AnyFrame> {
+        if self.config.fd {
+            let flags: u8 = if self.config.brs { CANFD_BRS } else { 0 };
+            CanFdFrame::with_can_id(self.config.host_id, data_bytes, flags)
+                .ok()
+                .map(AnyFrame::Fd)
+        } else {
+            new_can_frame(self.config.host_id, data_bytes)
+                .ok()
+                .map(AnyFrame::Classic)
+        }
+    }
+}
+
+/// Progress of a running test, reported periodically
+struct Progress {
+    /// time of the last update
+    last_update: Instant,
+    /// frames received from the DUT until the last update
+    received: usize,
+    /// data bytes until the last update
+    payload_bytes: usize,
+}
+
+impl Progress {
+    fn new(start: Instant) -> Progress {
+        Progress {
+            last_update: start,
+            received: 0,
+            payload_bytes: 0,
+        }
+    }
+
+    fn due(&self, interval: Duration) -> bool {
+        self.last_update.elapsed() >= interval
+    }
+
+    /// Returns the progress with rates since the last update
+    fn update(&mut self, summary: &HostSummary, inflight: usize) -> HostProgress {
+        let elapsed: Duration = self.last_update.elapsed();
+        let report: HostProgress = HostProgress {
+            sent: summary.sent,
+            received: summary.received,
+            frames_per_second: rate(summary.received - self.received, elapsed),
+            throughput_kbit: rate((summary.payload_bytes - self.payload_bytes) * 8, elapsed) / 1000.0,
+            inflight,
+        };
+        self.last_update = Instant::now();
+        self.received = summary.received;
+        self.payload_bytes = summary.payload_bytes;
+        report
+    }
+}
+
+/// Compares two frames without logging, used to resynchronize in tolerant mode
+fn frames_equal(expected: &AnyFrame, received: &AnyFrame) -> bool {
+    let fd_flags = |frame: &AnyFrame| match frame {
+        AnyFrame::Classic(_) => None,
+        AnyFrame::Fd(frame) => Some(frame.flags()),
+    };
+    fd_flags(expected) == fd_flags(received)
+        && expected.id() == received.id()
+        && expected.data() == received.data()
+}
+
+/// Frame sent by the host which is not yet answered by the DUT
+#[derive(Copy, Clone)]
+struct InflightFrame {
+    frame: AnyFrame,
+    /// time the frame was sent
+    sent: Instant,
+    sequence: usize,
+    /// `true` if the frame was already received as own frame
+    own_received: bool,
+}
+
+/// Response of the DUT matched to a sent frame
+#[derive(Debug)]
+struct Response {
+    /// round trip latency of the frame
+    latency: Duration,
+    /// `false` if the response does not belong to the oldest frame or was
+    /// received before the own frame (tolerant mode only)
+    in_order: bool,
+}
+
+/// Frames sent by the host which are not yet answered by the DUT
+struct InflightFrames {
+    frames: VecDeque<InflightFrame>,
+    /// sequence number of the next frame sent
+    next_sequence: usize,
+    /// ID of the responses of the DUT
+    dut_id: CanId,
+    /// resynchronize on frames received out of order
+    tolerant: bool,
+    /// first received frame not matching the expected frame
+    first_mismatch: Option<FrameMismatch>,
+}
+
+impl InflightFrames {
+    fn new(capacity: usize, dut_id: CanId, tolerant: bool) -> InflightFrames {
+        InflightFrames {
+            frames: VecDeque::with_capacity(capacity),
+            next_sequence: 0,
+            dut_id,
+            tolerant,
+            first_mismatch: None,
+        }
+    }
+
+    fn mismatch(&mut self, sequence: usize, expected: AnyFrame, received: AnyFrame) {
+        if self.first_mismatch.is_none() {
+            self.first_mismatch = Some(FrameMismatch { sequence, expected, received });
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    fn push(&mut self, frame: AnyFrame) {
+        self.frames.push_back(InflightFrame {
+            frame,
+            sent: Instant::now(),
+            sequence: self.next_sequence,
+            own_received: false,
+        });
+        self.next_sequence += 1;
+    }
+
+    /// Drops the oldest frame, e.g. after it expired
+    fn drop_front(&mut self) {
+        self.frames.pop_front();
+    }
+
+    /// Returns the sequence number of the oldest frame if it was not answered in time
+    fn expired(&self, timeout: Duration) -> Option<usize> {
+        let inflight: &InflightFrame = self.frames.front()?;
+        if inflight.sent.elapsed() < timeout {
+            return None;
+        }
+        if !inflight.own_received {
+            log::error!("Frame lost! Sequence number: {}, sent frame: {}, own frame not received within {} ms",
+                inflight.sequence, inflight.frame, timeout.as_millis());
+        } else {
+            log::error!("Frame lost! Sequence number: {}, sent frame: {}, no response of DUT within {} ms",
+                inflight.sequence, inflight.frame, timeout.as_millis());
+        }
+        Some(inflight.sequence)
+    }
+
+    /// Own frames have to be received in the order they were sent
+    ///
+    /// Returns `Ok(false)` if the own frame belongs to a later frame (tolerant mode only).
+    fn own_frame(&mut self, received_frame: AnyFrame) -> Result<bool, FailureReason> {
+        let index: usize = match self.frames.iter().position(|inflight| !inflight.own_received) {
+            Some(index) => index,
+            None => {
+                log::error!("Received own frame {} which was not sent! Sequence number: {}",
+                    received_frame, self.next_sequence);
+                return Err(FailureReason::Sequence);
+            },
+        };
+        let expected: InflightFrame = self.frames[index];
+        if let Err(e) = Host::compare_frame(expected.frame, received_frame, 0) {
+            log::error!("Own frame mismatch! Sequence number: {}, sent frame: {}, own frame: {}",
+                expected.sequence, expected.frame, received_frame);
+            if self.tolerant {
+                // own frames of the frames in between got lost
+                let later = self.frames.iter_mut()
+                    .skip(index + 1)
+                    .find(|inflight| !inflight.own_received && frames_equal(&inflight.frame, &received_frame));
+                if let Some(inflight) = later {
+                    log::warn!("Resynchronized on own frame with sequence number {}", inflight.sequence);
+                    inflight.own_received = true;
+                    return Ok(false);
+                }
+            }
+            self.mismatch(expected.sequence, expected.frame, received_frame);
+            return Err(e.reason().unwrap_or(FailureReason::DataMismatch));
+        }
+        self.frames[index].own_received = true;
+        Ok(true)
+    }
+
+    /// DUT frames are only valid after the according own frame was received
+    ///
+    /// In tolerant mode the response may belong to any frame in flight.
+    fn dut_frame(&mut self, received_frame: AnyFrame) -> Result<Response, FailureReason> {
+        let expected: InflightFrame = match self.frames.front() {
+            Some(inflight) => *inflight,
+            None => {
+                log::error!("Received DUT frame {} without sending! Sequence number: {}",
+                    received_frame, self.next_sequence);
+                return Err(FailureReason::Sequence);
+            },
+        };
+        if self.tolerant {
+            let dut_id: CanId = self.dut_id;
+            let position: Option<usize> = self.frames.iter().position(|inflight| {
+                super::response_frame(inflight.frame, dut_id)
+                    .is_some_and(|response| frames_equal(&response, &received_frame))
+            });
+            if let Some(index) = position {
+                let inflight: InflightFrame = self.frames.remove(index).unwrap();
+                let in_order: bool = index == 0 && inflight.own_received;
+                if !in_order {
+                    log::warn!("Resynchronized on DUT frame with sequence number {}", inflight.sequence);
+                }
+                return Ok(Response { latency: inflight.sent.elapsed(), in_order });
+            }
+        }
+        if !expected.own_received {
+            log::error!("Did not receive own frame! Rx before Tx! Sequence number: {}, sent frame: {}, DUT frame: {}",
+                expected.sequence, expected.frame, received_frame);
+            self.mismatch(expected.sequence, expected.frame, received_frame);
+            return Err(FailureReason::Sequence);
+        }
+        self.frames.pop_front();
+        let expected_response: AnyFrame = match super::response_frame(expected.frame, self.dut_id) {
+            Some(f) => f,
+            None => {
+                log::error!("Could not create expected response of DUT! Sent frame: {}", expected.frame);
+                return Err(FailureReason::IdMismatch);
+            },
+        };
+        match Host::compare_frame(expected_response, received_frame, 0) {
+            Ok(_) => Ok(Response { latency: expected.sent.elapsed(), in_order: true }),
+            Err(e) => {
+                self.mismatch(expected.sequence, expected_response, received_frame);
+                Err(e.reason().unwrap_or(FailureReason::DataMismatch))
+            },
+        }
+    }
+}
+
+#[test]
+fn test_compare_self() {
+    let test_frame: AnyFrame = CANFrame::new(0x77, &[1, 2, 3, 4, 5, 6, 7, 8], false, false)
+        .unwrap()
+        .into();
+    assert!(Host::compare_frame(test_frame, test_frame, 0).unwrap());
+}
+
+#[test]
+fn test_compare_incremented_received() {
+    let expected_frame: CANFrame = CANFrame::new(0x77, &[1, 2, 3, 4, 5, 6, 7, 8], false, false)
+        .unwrap();
+    let test_frame: CANFrame = CANFrame::new(0x78, &[2, 3, 4, 5, 6, 7, 8, 9], false, false)
+        .unwrap();
+    assert!(Host::compare_frame(expected_frame.into(), test_frame.into(), 1).unwrap());
+}
+
+impl fmt::Display for HostError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.details)
+    }
+}
+
+impl error::Error for HostError {}
+
+#[test]
+fn test_false_incremented_received() {
+    let expected_frame: CANFrame = CANFrame::new(0x77, &[1, 2, 3, 4, 5, 6, 7, 8], false, false)
+        .unwrap();
+    let test_frame: CANFrame = CANFrame::new(0x78, &[2, 3, 4, 5, 7, 8, 9, 10], false, false)
+        .unwrap();
+    assert!(!Host::compare_frame(expected_frame.into(), test_frame.into(), 1).unwrap());
+}
+
+#[test]
+fn test_host_run_loopback() {
+    let bus: LoopbackBus = LoopbackBus::new();
+    let host_transport = bus.connect();
+    let dut = bus.connect();
+    let config: HostConfig = HostConfig {
+        inflight_count: 5,
+        ..HostConfig::default()
+    };
+    let host: Host<_> = Host::with_transport(host_transport, config)
+        .unwrap();
+    let host_thread = thread::spawn(move || host.run());
+    for _ in 0..20 {
+        let frame: AnyFrame = dut.read_frame()
+            .unwrap();
+        assert_eq!(0x77, frame.id());
+        dut.write_frame(&super::increment_frame(frame).unwrap())
+            .unwrap();
+    }
+    // answering without incrementing the data bytes stops the host
+    let frame: AnyFrame = dut.read_frame()
+        .unwrap();
+    dut.write_frame(&CANFrame::new(0x78, frame.data(), false, false).unwrap().into())
+        .unwrap();
+    let failure: HostFailure = host_thread.join()
+        .unwrap()
+        .unwrap_err();
+    assert_eq!(FailureReason::DataMismatch, failure.reason);
+    assert!(!failure.summary.passed());
+    assert_eq!(21, failure.summary.received);
+    assert_eq!(1, failure.summary.mismatches);
+}
+
+#[test]
+fn test_host_run_frame_count() {
+    let bus: LoopbackBus = LoopbackBus::new();
+    let config: HostConfig = HostConfig {
+        inflight_count: 5,
+        frame_count: 12,
+        ..HostConfig::default()
+    };
+    let host: Host<_> = Host::with_transport(bus.connect(), config)
+        .unwrap();
+    let dut = bus.connect();
+    let host_thread = thread::spawn(move || host.run());
+    for _ in 0..12 {
+        let frame: AnyFrame = dut.read_frame()
+            .unwrap();
+        dut.write_frame(&super::increment_frame(frame).unwrap())
+            .unwrap();
+    }
+    let summary: HostSummary = host_thread.join()
+        .unwrap()
+        .unwrap();
+    assert!(summary.passed());
+    assert_eq!(12, summary.sent);
+    assert_eq!(12, summary.received);
+    assert_eq!(12, summary.latency.count());
+    assert_eq!(12 * 8 * 2, summary.payload_bytes);
+    // no frames are sent after the last test frame
+    dut.set_read_timeout(Some(Duration::from_millis(10)))
+        .unwrap();
+    assert!(dut.read_frame().is_err());
+}
+
+#[test]
+fn test_own_frame_before_dut_frame() {
+    let sent_frame: AnyFrame = CANFrame::new(0x77, &[1, 2, 3, 4, 5, 6, 7, 8], false, false)
+        .unwrap()
+        .into();
+    let dut_frame: AnyFrame = super::increment_frame(sent_frame)
+        .unwrap();
+    let mut tx_frames: InflightFrames = InflightFrames::new(2, CanId::from_raw(DEFAULT_DUT_ID), false);
+    tx_frames.push(sent_frame);
+    tx_frames.push(sent_frame);
+    tx_frames.own_frame(sent_frame).unwrap();
+    assert!(tx_frames.dut_frame(dut_frame).is_ok());
+    // own frame of the second frame is still missing
+    assert_eq!(FailureReason::Sequence, tx_frames.dut_frame(dut_frame).unwrap_err());
+}
+
+#[test]
+fn test_own_frame_not_sent() {
+    let sent_frame: AnyFrame = CANFrame::new(0x77, &[1, 2, 3, 4, 5, 6, 7, 8], false, false)
+        .unwrap()
+        .into();
+    let mut tx_frames: InflightFrames = InflightFrames::new(1, CanId::from_raw(DEFAULT_DUT_ID), false);
+    tx_frames.push(sent_frame);
+    tx_frames.own_frame(sent_frame).unwrap();
+    assert_eq!(Err(FailureReason::Sequence), tx_frames.own_frame(sent_frame));
+}
+
+#[test]
+fn test_host_run_lost_frame() {
+    let bus: LoopbackBus = LoopbackBus::new();
+    let config: HostConfig = HostConfig {
+        inflight_count: 1,
+        frame_count: 10,
+        response_timeout: Duration::from_millis(50),
+        ..HostConfig::default()
+    };
+    let host: Host<_> = Host::with_transport(bus.connect(), config)
+        .unwrap();
+    let dut = bus.connect();
+    let host_thread = thread::spawn(move || host.run());
+    for _ in 0..3 {
+        let frame: AnyFrame = dut.read_frame()
+            .unwrap();
+        dut.write_frame(&super::increment_frame(frame).unwrap())
+            .unwrap();
+    }
+    // fourth frame is not answered
+    let failure: HostFailure = host_thread.join()
+        .unwrap()
+        .unwrap_err();
+    assert_eq!(FailureReason::Timeout, failure.reason);
+    assert!(!failure.summary.passed());
+    assert_eq!(3, failure.summary.received);
+    assert_eq!(Some(3), failure.summary.first_lost);
+    assert_eq!(1, failure.summary.lost_frames);
+}
+
+#[test]
+fn test_compare_frame_type_mismatch() {
+    let classic_frame: AnyFrame = CANFrame::new(0x77, &[1, 2, 3, 4, 5, 6, 7, 8], false, false)
+        .unwrap()
+        .into();
+    let fd_frame: AnyFrame = CanFdFrame::new(0x77, &[1, 2, 3, 4, 5, 6, 7, 8], 0)
+        .unwrap()
+        .into();
+    let brs_frame: AnyFrame = CanFdFrame::new(0x77, &[1, 2, 3, 4, 5, 6, 7, 8], CANFD_BRS)
+        .unwrap()
+        .into();
+    assert!(Host::compare_frame(classic_frame, fd_frame, 0).is_err());
+    assert!(Host::compare_frame(fd_frame, brs_frame, 0).is_err());
+}
+
+#[test]
+fn test_host_run_fd_loopback() {
+    let bus: LoopbackBus = LoopbackBus::new();
+    let config: HostConfig = HostConfig {
+        inflight_count: 5,
+        frame_count: CANFD_VALID_LENGTHS.len(),
+        fd: true,
+        brs: true,
+        ..HostConfig::default()
+    };
+    let host: Host<_> = Host::with_transport(bus.connect(), config)
+        .unwrap();
+    let dut = bus.connect();
+    dut.set_fd_frames(true)
+        .unwrap();
+    let host_thread = thread::spawn(move || host.run());
+    for len in CANFD_VALID_LENGTHS.iter() {
+        let frame: CanFdFrame = dut.read_frame()
+            .unwrap()
+            .fd()
+            .unwrap();
+        assert_eq!(*len, frame.data().len());
+        assert!(frame.is_brs());
+        dut.write_frame(&super::increment_frame(frame.into()).unwrap())
+            .unwrap();
+    }
+    let summary: HostSummary = host_thread.join()
+        .unwrap()
+        .unwrap();
+    assert!(summary.passed());
+}
+
+#[test]
+fn test_host_gap() {
+    let bus: LoopbackBus = LoopbackBus::new();
+    let host: Host<_> = Host::with_transport(bus.connect(), HostConfig::default())
+        .unwrap();
+    assert_eq!(DEFAULT_FRAME_GAP, host.gap(32));
+    assert_eq!(DEFAULT_BURST_GAP, host.gap(33));
+    let config: HostConfig = HostConfig {
+        frame_gap: Duration::ZERO,
+        burst_length: 0,
+        ..HostConfig::default()
+    };
+    let host: Host<_> = Host::with_transport(bus.connect(), config)
+        .unwrap();
+    assert_eq!(Duration::ZERO, host.gap(33));
+}
+
+#[test]
+fn test_host_run_without_gap() {
+    let bus: LoopbackBus = LoopbackBus::new();
+    let config: HostConfig = HostConfig {
+        inflight_count: 10,
+        frame_count: 100,
+        frame_gap: Duration::ZERO,
+        burst_length: 0,
+        ..HostConfig::default()
+    };
+    let host: Host<_> = Host::with_transport(bus.connect(), config)
+        .unwrap();
+    let dut = bus.connect();
+    let host_thread = thread::spawn(move || host.run());
+    for _ in 0..100 {
+        let frame: AnyFrame = dut.read_frame()
+            .unwrap();
+        dut.write_frame(&super::increment_frame(frame).unwrap())
+            .unwrap();
+    }
+    let summary: HostSummary = host_thread.join()
+        .unwrap()
+        .unwrap();
+    assert!(summary.passed());
+    assert_eq!(100, summary.received);
+}
+
+#[test]
+fn test_host_run_progress_handler() {
+    let bus: LoopbackBus = LoopbackBus::new();
+    let config: HostConfig = HostConfig {
+        frame_count: 20,
+        frame_gap: Duration::from_millis(5),
+        progress_interval: Some(Duration::from_millis(10)),
+        ..HostConfig::default()
+    };
+    let mut host: Host<_> = Host::with_transport(bus.connect(), config)
+        .unwrap();
+    let reports: Arc<Mutex<Vec<HostProgress>>> = Arc::new(Mutex::new(Vec::new()));
+    let handler_reports: Arc<Mutex<Vec<HostProgress>>> = reports.clone();
+    host.on_progress(move |progress| handler_reports.lock().unwrap().push(progress.clone()));
+    let dut = bus.connect();
+    let host_thread = thread::spawn(move || host.run());
+    for _ in 0..20 {
+        let frame: AnyFrame = dut.read_frame()
+            .unwrap();
+        dut.write_frame(&super::increment_frame(frame).unwrap())
+            .unwrap();
+    }
+    assert!(host_thread.join().unwrap().unwrap().passed());
+    let reports = reports.lock().unwrap();
+    assert!(!reports.is_empty());
+    assert!(reports.windows(2).all(|pair| pair[0].sent <= pair[1].sent));
+    assert!(reports.iter().all(|report| report.received <= 20));
+}
+
+#[test]
+fn test_host_run_stop_flag() {
+    let bus: LoopbackBus = LoopbackBus::new();
+    let config: HostConfig = HostConfig {
+        inflight_count: 5,
+        ..HostConfig::default()
+    };
+    let host: Host<_> = Host::with_transport(bus.connect(), config)
+        .unwrap();
+    let stop: Arc<AtomicBool> = host.stop_flag();
+    let dut = bus.connect();
+    dut.set_read_timeout(Some(Duration::from_millis(200)))
+        .unwrap();
+    let host_thread = thread::spawn(move || host.run());
+    for _ in 0..10 {
+        let frame: AnyFrame = dut.read_frame()
+            .unwrap();
+        dut.write_frame(&super::increment_frame(frame).unwrap())
+            .unwrap();
+    }
+    stop.store(true, Ordering::SeqCst);
+    // answer the frames still in flight
+    while let Ok(frame) = dut.read_frame() {
+        dut.write_frame(&super::increment_frame(frame).unwrap())
+            .unwrap();
+    }
+    let summary: HostSummary = host_thread.join()
+        .unwrap()
+        .unwrap();
+    assert!(summary.passed());
+    assert!(summary.sent >= 10);
+    assert_eq!(summary.sent, summary.received);
+}
+
+#[test]
+fn test_host_run_duration() {
+    let bus: LoopbackBus = LoopbackBus::new();
+    let config: HostConfig = HostConfig {
+        duration: Some(Duration::from_millis(50)),
+        ..HostConfig::default()
+    };
+    let host: Host<_> = Host::with_transport(bus.connect(), config)
+        .unwrap();
+    let dut = bus.connect();
+    dut.set_read_timeout(Some(Duration::from_millis(200)))
+        .unwrap();
+    let host_thread = thread::spawn(move || host.run());
+    while let Ok(frame) = dut.read_frame() {
+        dut.write_frame(&super::increment_frame(frame).unwrap())
+            .unwrap();
+    }
+    let summary: HostSummary = host_thread.join()
+        .unwrap()
+        .unwrap();
+    assert!(summary.passed());
+    assert!(summary.sent > 0);
+    assert_eq!(summary.sent, summary.received);
+}
+
+#[test]
+fn test_host_run_bus_events() {
+    let bus: LoopbackBus = LoopbackBus::new();
+    let config: HostConfig = HostConfig {
+        inflight_count: 1,
+        frame_count: 2,
+        ..HostConfig::default()
+    };
+    let host: Host<_> = Host::with_transport(bus.connect(), config)
+        .unwrap();
+    let dut = bus.connect();
+    let host_thread = thread::spawn(move || host.run());
+    // error frame with error-passive controller state as reported by the driver
+    let error_frame: CANFrame = CANFrame::new(0x04, &[0, 0x20, 0, 0, 0, 0, 0, 0], false, true)
+        .unwrap();
+    for _ in 0..2 {
+        let frame: AnyFrame = dut.read_frame()
+            .unwrap();
+        dut.write_frame(&error_frame.into())
+            .unwrap();
+        dut.write_frame(&super::increment_frame(frame).unwrap())
+            .unwrap();
+    }
+    let summary: HostSummary = host_thread.join()
+        .unwrap()
+        .unwrap();
+    assert!(summary.passed());
+    assert_eq!(2, summary.bus_events.get(BusEvent::ErrorPassive).unwrap().count);
+    assert!(summary.to_string().contains("Bus events: error-passive 2x"));
+}
+
+#[test]
+fn test_host_run_random_pattern() {
+    let bus: LoopbackBus = LoopbackBus::new();
+    let config: HostConfig = HostConfig {
+        inflight_count: 5,
+        frame_count: 50,
+        frame_gap: Duration::ZERO,
+        pattern: Pattern::Random,
+        seed: 1234,
+        random_length: true,
+        ..HostConfig::default()
+    };
+    let host: Host<_> = Host::with_transport(bus.connect(), config)
+        .unwrap();
+    let dut = bus.connect();
+    let host_thread = thread::spawn(move || host.run());
+    let mut lengths: Vec<usize> = Vec::new();
+    for _ in 0..50 {
+        let frame: AnyFrame = dut.read_frame()
+            .unwrap();
+        assert!(Pattern::Random.validate(frame.data()));
+        lengths.push(frame.data().len());
+        dut.write_frame(&super::increment_frame(frame).unwrap())
+            .unwrap();
+    }
+    let summary: HostSummary = host_thread.join()
+        .unwrap()
+        .unwrap();
+    assert!(summary.passed());
+    assert!(lengths.iter().any(|len| *len != lengths[0]));
+}
+
+#[test]
+fn test_host_run_extended_ids() {
+    let bus: LoopbackBus = LoopbackBus::new();
+    let host_id: CanId = CanId::extended(0x77).unwrap();
+    let dut_id: CanId = CanId::extended(0x1abcdef).unwrap();
+    let config: HostConfig = HostConfig {
+        frame_count: 5,
+        host_id,
+        dut_id,
+        ..HostConfig::default()
+    };
+    let host: Host<_> = Host::with_transport(bus.connect(), config)
+        .unwrap();
+    let dut = bus.connect();
+    let host_thread = thread::spawn(move || host.run());
+    for _ in 0..5 {
+        let frame: AnyFrame = dut.read_frame()
+            .unwrap();
+        assert_eq!(host_id, frame.can_id());
+        dut.write_frame(&super::response_frame(frame, dut_id).unwrap())
+            .unwrap();
+    }
+    let summary: HostSummary = host_thread.join()
+        .unwrap()
+        .unwrap();
+    assert!(summary.passed());
+}
+
+#[test]
+fn test_host_same_ids() {
+    let bus: LoopbackBus = LoopbackBus::new();
+    let config: HostConfig = HostConfig {
+        dut_id: CanId::from_raw(DEFAULT_HOST_ID),
+        ..HostConfig::default()
+    };
+    assert!(Host::with_transport(bus.connect(), config).is_err());
+}
+
+#[test]
+fn test_host_run_foreign_id() {
+    let bus: LoopbackBus = LoopbackBus::new();
+    let config: HostConfig = HostConfig {
+        frame_count: 1,
+        ..HostConfig::default()
+    };
+    let host: Host<_> = Host::with_transport(bus.connect(), config)
+        .unwrap();
+    let dut = bus.connect();
+    let host_thread = thread::spawn(move || host.run());
+    let frame: AnyFrame = dut.read_frame()
+        .unwrap();
+    // frames of other nodes on the bus do not stop the test
+    dut.write_frame(&CANFrame::new(0x123, &[1, 1, 3], false, false).unwrap().into())
+        .unwrap();
+    dut.write_frame(&super::response_frame(frame, CanId::standard(0x79).unwrap()).unwrap())
+        .unwrap();
+    dut.write_frame(&super::increment_frame(frame).unwrap())
+        .unwrap();
+    let summary: HostSummary = host_thread.join()
+        .unwrap()
+        .unwrap();
+    assert!(summary.passed());
+    assert_eq!(1, summary.received);
+    assert_eq!(2, summary.ignored);
+    assert_eq!(0, summary.mismatches);
+}
+
+#[test]
+fn test_progress_update() {
+    let mut progress: Progress = Progress::new(Instant::now() - Duration::from_secs(2));
+    assert!(progress.due(Duration::from_secs(1)));
+    let summary: HostSummary = HostSummary {
+        sent: 210,
+        received: 200,
+        payload_bytes: 3200,
+        ..HostSummary::default()
+    };
+    let line: String = progress.update(&summary, 10).to_string();
+    assert!(line.starts_with("Sent: 210, received: 200, 100.0 frames/s, payload throughput: 12.8 kbit/s"));
+    assert!(line.ends_with("in flight: 10"));
+    assert!(!progress.due(Duration::from_secs(1)));
+}
+
+#[test]
+fn test_host_run_tolerant() {
+    let bus: LoopbackBus = LoopbackBus::new();
+    let config: HostConfig = HostConfig {
+        inflight_count: 1,
+        frame_count: 10,
+        response_timeout: Duration::from_millis(50),
+        tolerant: true,
+        ..HostConfig::default()
+    };
+    let host: Host<_> = Host::with_transport(bus.connect(), config)
+        .unwrap();
+    let dut = bus.connect();
+    let host_thread = thread::spawn(move || host.run());
+    dut.write_frame(&CANFrame::new(0x123, &[1, 2, 3], false, false).unwrap().into())
+        .unwrap();
+    for sequence in 0..10 {
+        let frame: AnyFrame = dut.read_frame()
+            .unwrap();
+        match sequence {
+            // wrong data bytes
+            1 => dut.write_frame(&CANFrame::new(0x78, frame.data(), false, false).unwrap().into()),
+            // not answered at all
+            2 => continue,
+            _ => dut.write_frame(&super::increment_frame(frame).unwrap()),
+        }.unwrap();
+    }
+    let failure: HostFailure = host_thread.join()
+        .unwrap()
+        .unwrap_err();
+    assert_eq!(FailureReason::DataMismatch, failure.reason);
+    assert!(failure.summary.completed);
+    assert_eq!(10, failure.summary.sent);
+    assert_eq!(9, failure.summary.received);
+    assert_eq!(1, failure.summary.mismatches);
+    assert_eq!(1, failure.summary.lost_frames);
+    assert_eq!(Some(2), failure.summary.first_lost);
+    assert_eq!(1, failure.summary.ignored);
+}
+
+#[test]
+fn test_dut_frame_resync() {
+    let first: AnyFrame = CANFrame::new(0x77, &[1, 2, 3, 4, 5, 6, 7, 8], false, false)
+        .unwrap()
+        .into();
+    let second: AnyFrame = CANFrame::new(0x77, &[2, 3, 4, 5, 6, 7, 8, 9], false, false)
+        .unwrap()
+        .into();
+    let mut tx_frames: InflightFrames = InflightFrames::new(2, CanId::from_raw(DEFAULT_DUT_ID), true);
+    tx_frames.push(first);
+    tx_frames.push(second);
+    // own frame of the first frame got lost
+    assert!(!tx_frames.own_frame(second).unwrap());
+    assert!(!tx_frames.dut_frame(super::increment_frame(second).unwrap()).unwrap().in_order);
+    assert!(!tx_frames.dut_frame(super::increment_frame(first).unwrap()).unwrap().in_order);
+    assert_eq!(0, tx_frames.len());
+}
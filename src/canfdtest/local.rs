@@ -0,0 +1,127 @@
+//! Host and DUT running in the same process
+//!
+//! Host and DUT are connected to different transports, e.g. both ends of a
+//! vxcan pair or the two sides of a gateway, and run in separate threads.
+
+use super::dut::{Dut, DutFailure, DutSummary};
+use super::host::{Host, HostFailure, HostSummary};
+#[cfg(test)]
+use super::dut::DutConfig;
+#[cfg(test)]
+use super::host::HostConfig;
+#[cfg(test)]
+use super::FailureReason;
+use crate::transport::CanTransport;
+#[cfg(test)]
+use crate::transport::LoopbackBus;
+use std::fmt;
+use std::sync::atomic::Ordering;
+use std::thread;
+#[cfg(test)]
+use std::time::Duration;
+
+/// Combined result of host and DUT
+#[derive(Debug)]
+pub struct LocalResult {
+    /// result of the host
+    pub host: Result<HostSummary, HostFailure>,
+    /// result of the DUT
+    pub dut: Result<DutSummary, DutFailure>,
+}
+
+impl LocalResult {
+    /// Check if host and DUT finished without errors
+    pub fn passed(&self) -> bool {
+        self.host.is_ok() && self.dut.is_ok()
+    }
+
+    /// Exit code of `canfdtest`, the failure of the host takes precedence over the DUT
+    pub fn exit_code(&self) -> i32 {
+        match (&self.host, &self.dut) {
+            (Err(failure), _) => failure.reason.exit_code(),
+            (Ok(_), Err(failure)) => failure.reason.exit_code(),
+            (Ok(_), Ok(_)) => 0,
+        }
+    }
+}
+
+impl fmt::Display for LocalResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.host {
+            Ok(summary) => writeln!(f, "Host:\n{}", summary)?,
+            Err(failure) => writeln!(f, "Host:\n{}", failure)?,
+        }
+        match &self.dut {
+            Ok(summary) => write!(f, "DUT:\n{}", summary),
+            Err(failure) => write!(f, "DUT:\n{}", failure),
+        }
+    }
+}
+
+/// Runs host and DUT in separate threads until the host finished the test
+///
+/// The DUT is stopped after the host finished, so the result of the DUT
+/// only contains errors if it stopped on its own.
+/// # Arguments
+/// * 'host' - host sending the test frames
+/// * 'dut' - DUT answering the test frames
+pub fn run_local<H, D>(host: Host<H>, dut: Dut<D>) -> LocalResult
+where
+    H: CanTransport + Send + 'static,
+    D: CanTransport + Send + 'static,
+{
+    let dut_stop = dut.stop_flag();
+    let dut_thread = thread::spawn(move || dut.run());
+    let host_thread = thread::spawn(move || host.run());
+    let host_result: Result<HostSummary, HostFailure> = host_thread.join()
+        .expect("host thread panicked");
+    log::info!("Host finished, stopping DUT.");
+    dut_stop.store(true, Ordering::SeqCst);
+    let dut_result: Result<DutSummary, DutFailure> = dut_thread.join()
+        .expect("DUT thread panicked");
+    LocalResult {
+        host: host_result,
+        dut: dut_result,
+    }
+}
+
+#[test]
+fn test_run_local() {
+    let bus: LoopbackBus = LoopbackBus::new();
+    let config: HostConfig = HostConfig {
+        frame_count: 20,
+        frame_gap: Duration::ZERO,
+        ..HostConfig::default()
+    };
+    let host: Host<_> = Host::with_transport(bus.connect(), config)
+        .unwrap();
+    let dut: Dut<_> = Dut::with_transport(bus.connect(), DutConfig::default())
+        .unwrap();
+    let result: LocalResult = run_local(host, dut);
+    assert!(result.passed());
+    assert_eq!(0, result.exit_code());
+    assert_eq!(20, result.dut.unwrap().answered);
+}
+
+#[test]
+fn test_run_local_failure() {
+    let bus: LoopbackBus = LoopbackBus::new();
+    let config: HostConfig = HostConfig {
+        frame_count: 20,
+        response_timeout: Duration::from_millis(100),
+        ..HostConfig::default()
+    };
+    let host: Host<_> = Host::with_transport(bus.connect(), config)
+        .unwrap();
+    // responses with an unrelated ID are ignored by the host
+    let dut_config: DutConfig = DutConfig {
+        dut_id: crate::id::CanId::standard(0x79).unwrap(),
+        ..DutConfig::default()
+    };
+    let dut: Dut<_> = Dut::with_transport(bus.connect(), dut_config)
+        .unwrap();
+    let result: LocalResult = run_local(host, dut);
+    assert!(!result.passed());
+    assert_eq!(FailureReason::Timeout.exit_code(), result.exit_code());
+    assert!(result.dut.is_ok());
+}
@@ -0,0 +1,201 @@
+//! Echo test between a host (generator) and a device under test (DUT)
+//!
+//! The host sends frames with continuous data bytes (or another selectable
+//! [`pattern`]), the DUT checks them and answers each of them with an
+//! incremented frame (every data byte plus one, sent with the DUT ID) which is
+//! then checked by the host. Both test IDs are configurable and
+//! may use the extended frame format.
+//!
+//! In CAN FD mode the host cycles through all valid CAN FD data lengths and
+//! the DUT answers with CAN FD frames using the same flags (e.g. bit rate
+//! switch).
+//!
+//! Host and DUT can also run in the same process on two transports, see
+//! [`local::run_local`].
+
+use crate::fd::CanFdFrame;
+use crate::frame::{new_can_frame, AnyFrame};
+use crate::id::CanId;
+#[cfg(test)]
+use socketcan::CANFrame;
+use std::fmt;
+
+pub mod dut;
+pub mod host;
+pub mod local;
+pub mod pattern;
+pub mod report;
+pub mod stats;
+
+/// Count of frames the host sends before waiting for responses of the DUT
+pub const DEFAULT_INFLIGHT_COUNT: usize = 50;
+
+/// ID of the frames sent by the host
+pub const DEFAULT_HOST_ID: u32 = 0x77;
+
+/// ID of the frames sent by the DUT
+pub const DEFAULT_DUT_ID: u32 = 0x78;
+
+/// Reason why an echo test failed
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum FailureReason {
+    /// a frame could not be sent
+    Send,
+    /// a frame could not be received
+    Receive,
+    /// a frame with an unexpected ID was received
+    IdMismatch,
+    /// a frame with unexpected data (or frame type) was received
+    DataMismatch,
+    /// a frame was not received in time
+    Timeout,
+    /// frames were received in an unexpected order, e.g. the response of the
+    /// DUT before the own frame of the host
+    Sequence,
+}
+
+impl FailureReason {
+    /// Exit code of `canfdtest` for the failure
+    ///
+    /// `1` is left for errors before the test starts, e.g. invalid arguments.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            FailureReason::Send => 2,
+            FailureReason::Receive => 3,
+            FailureReason::IdMismatch => 4,
+            FailureReason::DataMismatch => 5,
+            FailureReason::Timeout => 6,
+            FailureReason::Sequence => 7,
+        }
+    }
+}
+
+impl fmt::Display for FailureReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let description: &str = match self {
+            FailureReason::Send => "send error",
+            FailureReason::Receive => "receive error",
+            FailureReason::IdMismatch => "ID mismatch",
+            FailureReason::DataMismatch => "data mismatch",
+            FailureReason::Timeout => "timeout",
+            FailureReason::Sequence => "sequence error",
+        };
+        write!(f, "{}", description)
+    }
+}
+
+/// Increments ID and every data byte of a frame by one (data bytes wrap around)
+///
+/// CAN FD frames stay CAN FD frames with the same flags.
+/// # Arguments
+/// * 'frame' - frame to increment
+///
+/// # Examples
+/// ```
+/// use can_utils::canfdtest::increment_frame;
+/// use socketcan::CANFrame;
+///
+/// let frame = CANFrame::new(0x77, &[0x01, 0xff], false, false).unwrap();
+/// let incremented = increment_frame(frame.into()).unwrap();
+/// assert_eq!(0x78, incremented.id());
+/// assert_eq!(&[0x02, 0x00], incremented.data());
+/// ```
+pub fn increment_frame(frame: AnyFrame) -> Option<AnyFrame> {
+    let can_id: CanId = CanId::new(frame.id() + 1).ok()?;
+    response_frame(frame, can_id)
+}
+
+/// Creates the response of the DUT: every data byte incremented by one, sent with the given ID
+///
+/// CAN FD frames stay CAN FD frames with the same flags.
+/// # Arguments
+/// * 'frame' - frame received from the host
+/// * 'can_id' - ID of the response, including the frame format
+///
+/// # Examples
+/// ```
+/// use can_utils::canfdtest::response_frame;
+/// use can_utils::id::CanId;
+/// use socketcan::CANFrame;
+///
+/// let frame = CANFrame::new(0x77, &[0x01, 0xff], false, false).unwrap();
+/// let response = response_frame(frame.into(), CanId::extended(0x100).unwrap()).unwrap();
+/// assert!(response.is_extended());
+/// assert_eq!(&[0x02, 0x00], response.data());
+/// ```
+pub fn response_frame(frame: AnyFrame, can_id: CanId) -> Option<AnyFrame> {
+    let mut frame_data: Vec<u8> = vec![0; frame.data().len()];
+    frame_data[..].clone_from_slice(frame.data());
+    for byte in frame_data.iter_mut() {
+        // handled attempt to add with overflow
+        *byte = byte.wrapping_add(1);
+    }
+    match frame {
+        AnyFrame::Classic(_) => new_can_frame(can_id, &frame_data)
+            .ok()
+            .map(AnyFrame::Classic),
+        AnyFrame::Fd(fd_frame) => CanFdFrame::with_can_id(can_id, &frame_data, fd_frame.flags())
+            .ok()
+            .map(AnyFrame::Fd),
+    }
+}
+
+#[test]
+fn test_frame_increment() {
+    let host_frame: CANFrame = CANFrame::new(0x77, &[1, 2, 3, 4, 5, 6, 7, 8], false, false)
+        .unwrap();
+    let incremented_frame: AnyFrame = increment_frame(host_frame.into())
+        .unwrap();
+
+    assert_eq!(0x78, incremented_frame.id());
+    assert_eq!(&[2, 3, 4, 5, 6, 7, 8, 9], incremented_frame.data());
+}
+
+#[test]
+fn test_partial_frame_increment() {
+    // This should not occur during normal echo test, but it doesn't hurt to test it
+    let host_frame: CANFrame = CANFrame::new(0x77, &[1, 2, 3, 4], false, false)
+        .unwrap();
+    let incremented_frame: AnyFrame = increment_frame(host_frame.into())
+        .unwrap();
+
+    assert_eq!(0x78, incremented_frame.id());
+    assert_eq!(&[2, 3, 4, 5], incremented_frame.data());
+}
+
+#[test]
+fn test_overflow_increment() {
+    // test for problem fixed in commit '1af70af034f7c4c20ad63a5e3127875b9bee6533'
+    let host_frame: CANFrame = CANFrame::new(0x77, &[0xf9, 0xfa , 0xfb, 0xfc, 0xfd, 0xfe, 0xff, 0x00], false, false)
+        .unwrap();
+    let incremented_frame: AnyFrame = increment_frame(host_frame.into())
+        .unwrap();
+
+    assert_eq!(&[0xfa, 0xfb, 0xfc, 0xfd, 0xfe, 0xff, 0x00, 0x01], incremented_frame.data());
+}
+
+#[test]
+fn test_fd_frame_increment() {
+    let host_frame: CanFdFrame = CanFdFrame::new(0x77, &[0xff; 64], crate::fd::CANFD_BRS)
+        .unwrap();
+    let incremented_frame: CanFdFrame = increment_frame(host_frame.into())
+        .unwrap()
+        .fd()
+        .unwrap();
+
+    assert_eq!(0x78, incremented_frame.id());
+    assert_eq!(&[0x00; 64][..], incremented_frame.data());
+    assert!(incremented_frame.is_brs());
+}
+
+#[test]
+fn test_extended_response_frame() {
+    let host_frame: AnyFrame = new_can_frame(CanId::extended(0x77).unwrap(), &[1, 2, 3])
+        .unwrap()
+        .into();
+    let response: AnyFrame = response_frame(host_frame, CanId::extended(0x1234567).unwrap())
+        .unwrap();
+
+    assert_eq!(CanId::extended(0x1234567).unwrap(), response.can_id());
+    assert_eq!(&[2, 3, 4], response.data());
+}
@@ -0,0 +1,216 @@
+//! Data patterns of the test frames sent by the host
+//!
+//! Every pattern can be validated by the DUT without knowing the frames sent
+//! before, so lost frames do not affect the check of the following ones.
+//! Random frames start with up to 4 random key bytes, the remaining bytes are
+//! derived from them. At least the last byte is derived, so short frames are
+//! checked as well.
+
+use crate::fd::CANFD_VALID_LENGTHS;
+use crate::frame::CAN_MSG_SIZE;
+use std::fmt;
+
+/// Count of random bytes at the start of a random frame, the remaining bytes are derived from them
+const RANDOM_KEY_LEN: usize = 4;
+
+/// Count of random key bytes of a random frame with the given length, keeps at least one derived byte
+fn random_key_len(len: usize) -> usize {
+    len.saturating_sub(1).min(RANDOM_KEY_LEN)
+}
+
+/// Data pattern of the test frames
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum Pattern {
+    /// continuous data bytes starting at a counter incremented with every frame
+    #[default]
+    Counter,
+    /// pseudo random data bytes
+    Random,
+    /// all data bytes `0x00`, i.e. a stuff bit after every 5 bits
+    Zeros,
+    /// all data bytes `0xff`, i.e. a stuff bit after every 5 bits
+    Ones,
+}
+
+impl Pattern {
+    /// Check if data bytes follow the pattern
+    /// # Arguments
+    /// * 'data' - data bytes of a frame sent by the host
+    ///
+    /// # Examples
+    /// ```
+    /// use can_utils::canfdtest::pattern::Pattern;
+    ///
+    /// assert!(Pattern::Counter.validate(&[0xfe, 0xff, 0x00]));
+    /// assert!(!Pattern::Zeros.validate(&[0x00, 0x01]));
+    /// ```
+    pub fn validate(&self, data: &[u8]) -> bool {
+        match self {
+            Pattern::Counter => data.windows(2).all(|bytes| bytes[1] == bytes[0].wrapping_add(1)),
+            Pattern::Random => {
+                let key_len: usize = random_key_len(data.len());
+                let mut expected: Vec<u8> = data.to_vec();
+                fill_random_tail(&mut expected, key_len);
+                expected == data
+            },
+            Pattern::Zeros => data.iter().all(|byte| *byte == 0x00),
+            Pattern::Ones => data.iter().all(|byte| *byte == 0xff),
+        }
+    }
+}
+
+impl fmt::Display for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name: &str = match self {
+            Pattern::Counter => "counter",
+            Pattern::Random => "random",
+            Pattern::Zeros => "zeros",
+            Pattern::Ones => "ones",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Pseudo random number generator (SplitMix64), reproducible with the same seed
+#[derive(Debug, Clone)]
+struct Prng {
+    state: u64,
+}
+
+impl Prng {
+    fn new(seed: u64) -> Prng {
+        Prng { state: seed }
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z: u64 = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+}
+
+/// Derives the data bytes after the key bytes from the key bytes
+fn fill_random_tail(data: &mut [u8], key_len: usize) {
+    let mut key: [u8; 8] = [0; 8];
+    key[..key_len].copy_from_slice(&data[..key_len]);
+    let mut prng: Prng = Prng::new(u64::from_le_bytes(key));
+    for byte in data[key_len..].iter_mut() {
+        *byte = prng.next_u64() as u8;
+    }
+}
+
+/// Creates the data bytes of the test frames of the host
+#[derive(Debug, Clone)]
+pub struct PatternGenerator {
+    pattern: Pattern,
+    random_length: bool,
+    fd: bool,
+    counter: u8,
+    prng: Prng,
+}
+
+impl PatternGenerator {
+    /// Creates a generator, starting with the first frame of the test
+    /// # Arguments
+    /// * 'pattern' - data pattern of the frames
+    /// * 'seed' - seed of random data and random lengths
+    /// * 'random_length' - use random data lengths instead of the longest (classic CAN)
+    ///   or cycling through all valid lengths (CAN FD)
+    /// * 'fd' - create data for CAN FD frames
+    pub fn new(pattern: Pattern, seed: u64, random_length: bool, fd: bool) -> PatternGenerator {
+        PatternGenerator {
+            pattern,
+            random_length,
+            fd,
+            counter: 0,
+            prng: Prng::new(seed),
+        }
+    }
+
+    /// Data bytes of the next frame
+    /// # Arguments
+    /// * 'sequence' - sequence number of the frame
+    pub fn next_data(&mut self, sequence: usize) -> Vec<u8> {
+        let len: usize = match (self.fd, self.random_length) {
+            (true, true) => CANFD_VALID_LENGTHS[self.prng.next_u64() as usize % CANFD_VALID_LENGTHS.len()],
+            (true, false) => CANFD_VALID_LENGTHS[sequence % CANFD_VALID_LENGTHS.len()],
+            (false, true) => self.prng.next_u64() as usize % (CAN_MSG_SIZE + 1),
+            (false, false) => CAN_MSG_SIZE,
+        };
+        let data: Vec<u8> = match self.pattern {
+            Pattern::Counter => (0..len)
+                .map(|i| self.counter.wrapping_add(i as u8))
+                .collect(),
+            Pattern::Random => {
+                let key_len: usize = random_key_len(len);
+                let mut data: Vec<u8> = vec![0; len];
+                for byte in data[..key_len].iter_mut() {
+                    *byte = self.prng.next_u64() as u8;
+                }
+                fill_random_tail(&mut data, key_len);
+                data
+            },
+            Pattern::Zeros => vec![0x00; len],
+            Pattern::Ones => vec![0xff; len],
+        };
+        self.counter = self.counter.wrapping_add(1);
+        data
+    }
+}
+
+#[test]
+fn test_patterns_validate() {
+    for pattern in [Pattern::Counter, Pattern::Random, Pattern::Zeros, Pattern::Ones].iter() {
+        let mut generator: PatternGenerator = PatternGenerator::new(*pattern, 42, true, true);
+        for sequence in 0..100 {
+            let data: Vec<u8> = generator.next_data(sequence);
+            assert!(CANFD_VALID_LENGTHS.contains(&data.len()));
+            assert!(pattern.validate(&data), "{} {:x?}", pattern, data);
+        }
+    }
+}
+
+#[test]
+fn test_random_pattern_reproducible() {
+    let mut first: PatternGenerator = PatternGenerator::new(Pattern::Random, 1234, true, false);
+    let mut second: PatternGenerator = PatternGenerator::new(Pattern::Random, 1234, true, false);
+    let mut other: PatternGenerator = PatternGenerator::new(Pattern::Random, 4321, true, false);
+    let frames: Vec<Vec<u8>> = (0..20).map(|sequence| first.next_data(sequence)).collect();
+    assert_eq!(frames, (0..20).map(|sequence| second.next_data(sequence)).collect::<Vec<Vec<u8>>>());
+    assert_ne!(frames, (0..20).map(|sequence| other.next_data(sequence)).collect::<Vec<Vec<u8>>>());
+    assert!(frames.iter().all(|data| data.len() <= CAN_MSG_SIZE));
+    assert!(frames.iter().any(|data| data.len() != frames[0].len()));
+}
+
+#[test]
+fn test_random_pattern_corrupted() {
+    let mut generator: PatternGenerator = PatternGenerator::new(Pattern::Random, 7, false, false);
+    let mut data: Vec<u8> = generator.next_data(0);
+    assert!(Pattern::Random.validate(&data));
+    data[6] ^= 0x01;
+    assert!(!Pattern::Random.validate(&data));
+}
+
+#[test]
+fn test_random_pattern_corrupted_short() {
+    let mut generator: PatternGenerator = PatternGenerator::new(Pattern::Random, 7, true, false);
+    let mut checked: usize = 0;
+    for sequence in 0..50 {
+        let data: Vec<u8> = generator.next_data(sequence);
+        if data.is_empty() || data.len() > RANDOM_KEY_LEN {
+            continue;
+        }
+        assert!(Pattern::Random.validate(&data));
+        // the derived last byte does not match a corrupted frame
+        let mut corrupted: Vec<u8> = data.clone();
+        *corrupted.last_mut().unwrap() ^= 0x01;
+        assert!(!Pattern::Random.validate(&corrupted), "{:x?}", corrupted);
+        let mut corrupted: Vec<u8> = data.clone();
+        corrupted[0] ^= 0x80;
+        assert!(!Pattern::Random.validate(&corrupted), "{:x?}", corrupted);
+        checked += 1;
+    }
+    assert!(checked > 0);
+}
@@ -0,0 +1,268 @@
+//! Machine readable reports of an echo test run by the host
+//!
+//! Reports are written as JSON summary or as JUnit XML with a single test
+//! case, e.g. for CI integration. Both contain the settings of the host, the
+//! frame counts, the latency statistics and the first mismatching frame.
+
+use super::host::{FrameMismatch, HostConfig, HostFailure, HostSummary};
+use super::stats::{BusEventStats, LatencyStats};
+use super::FailureReason;
+#[cfg(test)]
+use crate::frame::AnyFrame;
+#[cfg(test)]
+use crate::error_frame::BusEvent;
+#[cfg(test)]
+use socketcan::CANFrame;
+use std::time::Duration;
+
+/// Escapes a string for use inside a JSON string
+fn json_escape(input: &str) -> String {
+    let mut escaped: String = String::with_capacity(input.len());
+    for character in input.chars() {
+        match character {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Escapes a string for use inside XML text and attributes
+fn xml_escape(input: &str) -> String {
+    let mut escaped: String = String::with_capacity(input.len());
+    for character in input.chars() {
+        match character {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+fn json_millis(duration: Option<Duration>) -> String {
+    match duration {
+        Some(duration) => format!("{:.3}", millis(duration)),
+        None => "null".to_string(),
+    }
+}
+
+/// Value of a setting of the host
+enum Parameter {
+    Number(u128),
+    Flag(bool),
+    Text(String),
+}
+
+impl Parameter {
+    fn raw(&self) -> String {
+        match self {
+            Parameter::Number(number) => number.to_string(),
+            Parameter::Flag(flag) => flag.to_string(),
+            Parameter::Text(text) => text.clone(),
+        }
+    }
+
+    fn json(&self) -> String {
+        match self {
+            Parameter::Text(text) => format!("\"{}\"", json_escape(text)),
+            _ => self.raw(),
+        }
+    }
+}
+
+/// Settings of the host as name/value pairs
+fn parameters(config: &HostConfig) -> Vec<(&'static str, Parameter)> {
+    vec![
+        ("inflight_count", Parameter::Number(config.inflight_count as u128)),
+        ("frame_count", Parameter::Number(config.frame_count as u128)),
+        ("response_timeout_ms", Parameter::Number(config.response_timeout.as_millis())),
+        ("fd", Parameter::Flag(config.fd)),
+        ("brs", Parameter::Flag(config.brs)),
+        ("tolerant", Parameter::Flag(config.tolerant)),
+        ("frame_gap_us", Parameter::Number(config.frame_gap.as_micros())),
+        ("burst_length", Parameter::Number(config.burst_length as u128)),
+        ("burst_gap_us", Parameter::Number(config.burst_gap.as_micros())),
+        ("duration_ms", Parameter::Number(config.duration.map_or(0, |duration| duration.as_millis()))),
+        ("pattern", Parameter::Text(config.pattern.to_string())),
+        ("seed", Parameter::Number(config.seed as u128)),
+        ("random_length", Parameter::Flag(config.random_length)),
+        ("extended", Parameter::Flag(config.host_id.is_extended())),
+        ("host_id", Parameter::Text(format!("{:X}", config.host_id.id()))),
+        ("dut_id", Parameter::Text(format!("{:X}", config.dut_id.id()))),
+    ]
+}
+
+fn summary_and_reason(result: &Result<HostSummary, HostFailure>) -> (&HostSummary, Option<FailureReason>) {
+    match result {
+        Ok(summary) => (summary, None),
+        Err(failure) => (&failure.summary, Some(failure.reason)),
+    }
+}
+
+fn json_latency(latency: &LatencyStats) -> String {
+    format!("{{\"count\": {}, \"min\": {}, \"avg\": {}, \"max\": {}, \"p50\": {}, \"p90\": {}, \"p99\": {}, \"p99_9\": {}}}",
+        latency.count(), json_millis(latency.min()), json_millis(latency.avg()), json_millis(latency.max()),
+        json_millis(latency.percentile(50.0)), json_millis(latency.percentile(90.0)),
+        json_millis(latency.percentile(99.0)), json_millis(latency.percentile(99.9)))
+}
+
+fn json_bus_events(bus_events: &BusEventStats) -> String {
+    let events: Vec<String> = bus_events.iter()
+        .map(|(event, count)| format!("\"{}\": {{\"count\": {}, \"first_s\": {:.3}, \"last_s\": {:.3}}}",
+            event.name(), count.count, count.first.as_secs_f64(), count.last.as_secs_f64()))
+        .collect();
+    format!("{{{}}}", events.join(", "))
+}
+
+fn json_mismatch(mismatch: &Option<FrameMismatch>) -> String {
+    match mismatch {
+        Some(mismatch) => format!("{{\"sequence\": {}, \"expected\": \"{}\", \"received\": \"{}\"}}",
+            mismatch.sequence, json_escape(&mismatch.expected.to_string()),
+            json_escape(&mismatch.received.to_string())),
+        None => "null".to_string(),
+    }
+}
+
+/// Creates the JSON summary of an echo test
+/// # Arguments
+/// * 'config' - settings of the host
+/// * 'result' - result of `Host::run`
+pub fn json_report(config: &HostConfig, result: &Result<HostSummary, HostFailure>) -> String {
+    let (summary, reason) = summary_and_reason(result);
+    let parameters: Vec<String> = parameters(config)
+        .iter()
+        .map(|(name, value)| format!("\"{}\": {}", name, value.json()))
+        .collect();
+    let mut json: String = String::from("{\n");
+    json.push_str(&format!("  \"result\": \"{}\",\n", if reason.is_none() { "passed" } else { "failed" }));
+    json.push_str(&format!("  \"failure_reason\": {},\n", match reason {
+        Some(reason) => format!("\"{}\"", json_escape(&reason.to_string())),
+        None => "null".to_string(),
+    }));
+    json.push_str(&format!("  \"parameters\": {{{}}},\n", parameters.join(", ")));
+    json.push_str(&format!("  \"sent\": {},\n", summary.sent));
+    json.push_str(&format!("  \"received\": {},\n", summary.received));
+    json.push_str(&format!("  \"mismatches\": {},\n", summary.mismatches));
+    json.push_str(&format!("  \"lost_frames\": {},\n", summary.lost_frames));
+    json.push_str(&format!("  \"sequence_errors\": {},\n", summary.sequence_errors));
+    json.push_str(&format!("  \"ignored\": {},\n", summary.ignored));
+    json.push_str(&format!("  \"first_lost\": {},\n", match summary.first_lost {
+        Some(sequence) => sequence.to_string(),
+        None => "null".to_string(),
+    }));
+    json.push_str(&format!("  \"elapsed_s\": {:.3},\n", summary.elapsed.as_secs_f64()));
+    json.push_str(&format!("  \"frame_rate\": {:.1},\n", summary.frame_rate()));
+    json.push_str(&format!("  \"throughput_kbit_s\": {:.1},\n", summary.throughput()));
+    json.push_str(&format!("  \"latency_ms\": {},\n", json_latency(&summary.latency)));
+    json.push_str(&format!("  \"bus_events\": {},\n", json_bus_events(&summary.bus_events)));
+    json.push_str(&format!("  \"first_mismatch\": {}\n", json_mismatch(&summary.first_mismatch)));
+    json.push_str("}\n");
+    json
+}
+
+/// Creates a JUnit XML report with a single test case for the echo test
+/// # Arguments
+/// * 'config' - settings of the host
+/// * 'result' - result of `Host::run`
+pub fn junit_report(config: &HostConfig, result: &Result<HostSummary, HostFailure>) -> String {
+    let (summary, reason) = summary_and_reason(result);
+    let time: f64 = summary.elapsed.as_secs_f64();
+    let mut xml: String = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    xml.push_str(&format!("<testsuites tests=\"1\" failures=\"{}\" time=\"{:.3}\">\n",
+        reason.is_some() as usize, time));
+    xml.push_str(&format!("  <testsuite name=\"canfdtest\" tests=\"1\" failures=\"{}\" time=\"{:.3}\">\n",
+        reason.is_some() as usize, time));
+    xml.push_str("    <properties>\n");
+    for (name, value) in parameters(config) {
+        xml.push_str(&format!("      <property name=\"{}\" value=\"{}\"/>\n", name, xml_escape(&value.raw())));
+    }
+    xml.push_str("    </properties>\n");
+    xml.push_str(&format!("    <testcase classname=\"canfdtest\" name=\"echo_test\" time=\"{:.3}\">\n", time));
+    if let Some(reason) = reason {
+        let mut details: String = format!("Failure reason: {}", reason);
+        if let Some(sequence) = summary.first_lost {
+            details.push_str(&format!("\nFirst lost frame: {}", sequence));
+        }
+        if let Some(mismatch) = &summary.first_mismatch {
+            details.push_str(&format!("\nFirst mismatch: sequence number {}, expected frame: {}, received frame: {}",
+                mismatch.sequence, mismatch.expected, mismatch.received));
+        }
+        if !summary.bus_events.is_empty() {
+            details.push_str(&format!("\n{}", summary.bus_events));
+        }
+        xml.push_str(&format!("      <failure message=\"{}\" type=\"{:?}\">{}</failure>\n",
+            xml_escape(&reason.to_string()), reason, xml_escape(&details)));
+    }
+    xml.push_str(&format!("      <system-out>{}</system-out>\n", xml_escape(&summary.to_string())));
+    xml.push_str("    </testcase>\n");
+    xml.push_str("  </testsuite>\n");
+    xml.push_str("</testsuites>\n");
+    xml
+}
+
+#[cfg(test)]
+fn failed_result() -> Result<HostSummary, HostFailure> {
+    let expected: AnyFrame = CANFrame::new(0x78, &[2, 3], false, false)
+        .unwrap()
+        .into();
+    let received: AnyFrame = CANFrame::new(0x78, &[2, 4], false, false)
+        .unwrap()
+        .into();
+    let mut summary: HostSummary = HostSummary {
+        sent: 3,
+        received: 2,
+        mismatches: 1,
+        first_mismatch: Some(FrameMismatch { sequence: 1, expected, received }),
+        ..HostSummary::default()
+    };
+    summary.bus_events.add(BusEvent::BusOff, Duration::from_millis(1500));
+    Err(HostFailure {
+        reason: FailureReason::DataMismatch,
+        summary: Box::new(summary),
+    })
+}
+
+#[test]
+fn test_json_report() {
+    let json: String = json_report(&HostConfig::default(), &failed_result());
+    assert!(json.contains("\"result\": \"failed\""));
+    assert!(json.contains("\"failure_reason\": \"data mismatch\""));
+    assert!(json.contains("\"inflight_count\": 50"));
+    assert!(json.contains("\"host_id\": \"77\""));
+    assert!(json.contains("\"latency_ms\": {\"count\": 0, \"min\": null"));
+    assert!(json.contains("\"bus_events\": {\"bus_off\": {\"count\": 1, \"first_s\": 1.500, \"last_s\": 1.500}}"));
+    assert!(json.contains("\"first_mismatch\": {\"sequence\": 1, \"expected\": \"078#0203\", \"received\": \"078#0204\"}"));
+    let passed: String = json_report(&HostConfig::default(), &Ok(HostSummary::default()));
+    assert!(passed.contains("\"failure_reason\": null"));
+}
+
+#[test]
+fn test_junit_report() {
+    let xml: String = junit_report(&HostConfig::default(), &failed_result());
+    assert!(xml.contains("<testsuite name=\"canfdtest\" tests=\"1\" failures=\"1\""));
+    assert!(xml.contains("<property name=\"dut_id\" value=\"78\"/>"));
+    assert!(xml.contains("<failure message=\"data mismatch\" type=\"DataMismatch\">"));
+    assert!(xml.contains("expected frame: 078#0203, received frame: 078#0204"));
+    assert!(xml.contains("Bus events: bus-off 1x"));
+    let passed: String = junit_report(&HostConfig::default(), &Ok(HostSummary::default()));
+    assert!(!passed.contains("<failure"));
+}
+
+#[test]
+fn test_escaping() {
+    assert_eq!("a\\\"b\\\\c\\n", json_escape("a\"b\\c\n"));
+    assert_eq!("&lt;a&gt; &amp; &quot;b&quot;", xml_escape("<a> & \"b\""));
+}
@@ -0,0 +1,232 @@
+//! Statistics of the echo test
+
+use crate::error_frame::BusEvent;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::time::Duration;
+
+/// Resolution of the latency percentiles
+const LATENCY_RESOLUTION: Duration = Duration::from_micros(10);
+
+/// Count of histogram buckets, latencies above 100 ms share the last bucket
+const LATENCY_BUCKETS: usize = 10_000;
+
+/// Round trip latencies from sending a frame until receiving the matching response of the DUT
+///
+/// Latencies are collected in a histogram, so memory usage does not grow
+/// with the duration of the test. Percentiles have a resolution of 10 µs.
+///
+/// # Examples
+/// ```
+/// use can_utils::canfdtest::stats::LatencyStats;
+/// use std::time::Duration;
+///
+/// let mut stats = LatencyStats::new();
+/// stats.add(Duration::from_micros(100));
+/// stats.add(Duration::from_micros(300));
+/// assert_eq!(Some(Duration::from_micros(200)), stats.avg());
+/// assert_eq!(Some(Duration::from_micros(300)), stats.max());
+/// ```
+#[derive(Debug, Clone)]
+pub struct LatencyStats {
+    count: usize,
+    min: Duration,
+    max: Duration,
+    total: Duration,
+    buckets: Vec<usize>,
+}
+
+impl Default for LatencyStats {
+    fn default() -> LatencyStats {
+        LatencyStats {
+            count: 0,
+            min: Duration::MAX,
+            max: Duration::ZERO,
+            total: Duration::ZERO,
+            buckets: vec![0; LATENCY_BUCKETS],
+        }
+    }
+}
+
+impl LatencyStats {
+    /// Creates empty statistics
+    pub fn new() -> LatencyStats {
+        LatencyStats::default()
+    }
+
+    /// Adds a measured latency
+    /// # Arguments
+    /// * 'latency' - time from sending a frame until receiving the response
+    pub fn add(&mut self, latency: Duration) {
+        let bucket: usize = (latency.as_nanos() / LATENCY_RESOLUTION.as_nanos()) as usize;
+        self.buckets[bucket.min(LATENCY_BUCKETS - 1)] += 1;
+        self.count += 1;
+        self.min = self.min.min(latency);
+        self.max = self.max.max(latency);
+        self.total += latency;
+    }
+
+    /// Count of measured latencies
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    /// Smallest latency, `None` without measurements
+    pub fn min(&self) -> Option<Duration> {
+        if self.count == 0 {
+            return None;
+        }
+        Some(self.min)
+    }
+
+    /// Largest latency, `None` without measurements
+    pub fn max(&self) -> Option<Duration> {
+        if self.count == 0 {
+            return None;
+        }
+        Some(self.max)
+    }
+
+    /// Average latency, `None` without measurements
+    pub fn avg(&self) -> Option<Duration> {
+        if self.count == 0 {
+            return None;
+        }
+        Some(self.total / self.count as u32)
+    }
+
+    /// Latency below which the given percentage of measurements lies, `None` without measurements
+    /// # Arguments
+    /// * 'percentile' - percentage between 0 and 100, e.g. `99.0`
+    pub fn percentile(&self, percentile: f64) -> Option<Duration> {
+        if self.count == 0 {
+            return None;
+        }
+        let rank: usize = ((percentile / 100.0 * self.count as f64).ceil() as usize).clamp(1, self.count);
+        let mut cumulated: usize = 0;
+        for (index, count) in self.buckets.iter().enumerate() {
+            cumulated += count;
+            if cumulated >= rank {
+                let upper_bound: Duration = LATENCY_RESOLUTION * (index as u32 + 1);
+                return Some(upper_bound.clamp(self.min, self.max));
+            }
+        }
+        Some(self.max)
+    }
+}
+
+/// Occurrences of a bus event, timestamps are relative to the start of the test
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct EventCount {
+    /// count of occurrences
+    pub count: usize,
+    /// time of the first occurrence
+    pub first: Duration,
+    /// time of the last occurrence
+    pub last: Duration,
+}
+
+/// Bus events (bus-off, error-passive, ...) reported by error frames during the test
+#[derive(Debug, Clone, Default)]
+pub struct BusEventStats {
+    events: BTreeMap<BusEvent, EventCount>,
+}
+
+impl BusEventStats {
+    /// Creates empty statistics
+    pub fn new() -> BusEventStats {
+        BusEventStats::default()
+    }
+
+    /// Adds an occurred event
+    /// # Arguments
+    /// * 'event' - decoded event of an error frame
+    /// * 'timestamp' - time since the start of the test
+    pub fn add(&mut self, event: BusEvent, timestamp: Duration) {
+        let count: &mut EventCount = self.events.entry(event)
+            .or_insert(EventCount { count: 0, first: timestamp, last: timestamp });
+        count.count += 1;
+        count.last = timestamp;
+    }
+
+    /// Occurrences of an event, `None` if it did not occur
+    pub fn get(&self, event: BusEvent) -> Option<&EventCount> {
+        self.events.get(&event)
+    }
+
+    /// Occurred events ordered by kind
+    pub fn iter(&self) -> impl Iterator<Item = (&BusEvent, &EventCount)> {
+        self.events.iter()
+    }
+
+    /// Check if no event occurred
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+}
+
+impl fmt::Display for BusEventStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return write!(f, "Bus events: none");
+        }
+        let events: Vec<String> = self.events.iter()
+            .map(|(event, count)| format!("{} {}x (first at {:.3} s, last at {:.3} s)",
+                event, count.count, count.first.as_secs_f64(), count.last.as_secs_f64()))
+            .collect();
+        write!(f, "Bus events: {}", events.join(", "))
+    }
+}
+
+fn millis(duration: Option<Duration>) -> f64 {
+    duration.unwrap_or_default().as_secs_f64() * 1000.0
+}
+
+impl fmt::Display for LatencyStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.count == 0 {
+            return write!(f, "Latency: no responses");
+        }
+        write!(f, "Latency min/avg/max: {:.3}/{:.3}/{:.3} ms, p50/p90/p99/p99.9: {:.3}/{:.3}/{:.3}/{:.3} ms",
+            millis(self.min()), millis(self.avg()), millis(self.max()),
+            millis(self.percentile(50.0)), millis(self.percentile(90.0)),
+            millis(self.percentile(99.0)), millis(self.percentile(99.9)))
+    }
+}
+
+#[test]
+fn test_latency_percentiles() {
+    let mut stats: LatencyStats = LatencyStats::new();
+    for micros in 1..=100 {
+        stats.add(Duration::from_micros(micros * 100));
+    }
+    assert_eq!(100, stats.count());
+    assert_eq!(Some(Duration::from_micros(100)), stats.min());
+    assert_eq!(Some(Duration::from_micros(10_000)), stats.max());
+    assert_eq!(Some(Duration::from_micros(5_050)), stats.avg());
+    assert_eq!(Some(Duration::from_micros(5_010)), stats.percentile(50.0));
+    assert_eq!(Some(Duration::from_micros(9_910)), stats.percentile(99.0));
+    assert_eq!(Some(Duration::from_micros(10_000)), stats.percentile(100.0));
+}
+
+#[test]
+fn test_latency_overflow() {
+    let mut stats: LatencyStats = LatencyStats::new();
+    stats.add(Duration::from_secs(2));
+    assert_eq!(Some(Duration::from_secs(2)), stats.percentile(50.0));
+    assert_eq!(None, LatencyStats::new().percentile(50.0));
+}
+
+#[test]
+fn test_bus_event_stats() {
+    let mut stats: BusEventStats = BusEventStats::new();
+    assert_eq!("Bus events: none", stats.to_string());
+    stats.add(BusEvent::BusOff, Duration::from_millis(1500));
+    stats.add(BusEvent::ErrorPassive, Duration::from_millis(1000));
+    stats.add(BusEvent::ErrorPassive, Duration::from_millis(2000));
+    assert_eq!(Some(&EventCount { count: 2, first: Duration::from_secs(1), last: Duration::from_secs(2) }),
+        stats.get(BusEvent::ErrorPassive));
+    assert_eq!(None, stats.get(BusEvent::AckError));
+    assert_eq!("Bus events: error-passive 2x (first at 1.000 s, last at 2.000 s), bus-off 1x (first at 1.500 s, last at 1.500 s)",
+        stats.to_string());
+}
@@ -0,0 +1,177 @@
+//! Cyclic transmission with drift-free timing
+//!
+//! The deadline of every cycle is calculated from the start of the
+//! transmission (absolute deadlines), so the time needed to send a frame does
+//! not add up over the cycles.
+
+use std::fmt;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Parses a transmission interval, e.g. `100ms`, `250us` or `100` (ms)
+/// # Arguments
+/// * 'interval' - interval with optional unit `ms` or `us`
+///
+/// # Examples
+/// ```
+/// use can_utils::cyclic::parse_interval;
+/// use std::time::Duration;
+///
+/// assert_eq!(Some(Duration::from_millis(100)), parse_interval("100ms"));
+/// assert_eq!(Some(Duration::from_micros(250)), parse_interval("250us"));
+/// assert_eq!(None, parse_interval("1s"));
+/// ```
+pub fn parse_interval(interval: &str) -> Option<Duration> {
+    let interval: &str = interval.trim();
+    if let Some(micros) = interval.strip_suffix("us") {
+        micros.parse().ok().map(Duration::from_micros)
+    } else {
+        interval.strip_suffix("ms")
+            .unwrap_or(interval)
+            .parse()
+            .ok()
+            .map(Duration::from_millis)
+    }
+}
+
+/// Deadlines of a cyclic transmission
+#[derive(Debug, Clone)]
+pub struct Schedule {
+    start: Instant,
+    interval: Duration,
+    cycle: u32,
+}
+
+impl Schedule {
+    /// Starts a schedule, the first deadline is now
+    /// # Arguments
+    /// * 'interval' - time between two deadlines
+    pub fn new(interval: Duration) -> Schedule {
+        Schedule {
+            start: Instant::now(),
+            interval,
+            cycle: 0,
+        }
+    }
+
+    /// Deadline of the next cycle
+    pub fn deadline(&self) -> Instant {
+        self.start + self.interval * self.cycle
+    }
+
+    /// Waits for the deadline of the next cycle
+    ///
+    /// Returns the delay after the deadline, i.e. the jitter of the cycle. A
+    /// cycle which is already late is not waited for, the following deadlines
+    /// stay the same.
+    pub fn wait(&mut self) -> Duration {
+        let deadline: Instant = self.deadline();
+        let now: Instant = Instant::now();
+        if deadline > now {
+            thread::sleep(deadline - now);
+        }
+        self.cycle += 1;
+        Instant::now().saturating_duration_since(deadline)
+    }
+
+    /// Time since the start of the schedule
+    pub fn elapsed(&self) -> Duration {
+        self.start.elapsed()
+    }
+}
+
+/// Delays of the cycles after their deadlines
+#[derive(Debug, Clone, Default)]
+pub struct JitterStats {
+    count: usize,
+    min: Duration,
+    max: Duration,
+    total: Duration,
+}
+
+impl JitterStats {
+    /// Creates empty statistics
+    pub fn new() -> JitterStats {
+        JitterStats::default()
+    }
+
+    /// Adds the delay of a cycle
+    /// # Arguments
+    /// * 'jitter' - delay after the deadline, e.g. returned by [`Schedule::wait`]
+    pub fn add(&mut self, jitter: Duration) {
+        if self.count == 0 || jitter < self.min {
+            self.min = jitter;
+        }
+        self.max = self.max.max(jitter);
+        self.total += jitter;
+        self.count += 1;
+    }
+
+    /// Count of cycles
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    /// Smallest delay, `None` without cycles
+    pub fn min(&self) -> Option<Duration> {
+        if self.count == 0 {
+            return None;
+        }
+        Some(self.min)
+    }
+
+    /// Largest delay, `None` without cycles
+    pub fn max(&self) -> Option<Duration> {
+        if self.count == 0 {
+            return None;
+        }
+        Some(self.max)
+    }
+
+    /// Average delay, `None` without cycles
+    pub fn avg(&self) -> Option<Duration> {
+        if self.count == 0 {
+            return None;
+        }
+        Some(self.total / self.count as u32)
+    }
+}
+
+fn micros(duration: Option<Duration>) -> f64 {
+    duration.unwrap_or_default().as_secs_f64() * 1_000_000.0
+}
+
+impl fmt::Display for JitterStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Jitter min/avg/max: {:.1}/{:.1}/{:.1} µs",
+            micros(self.min()), micros(self.avg()), micros(self.max()))
+    }
+}
+
+#[test]
+fn test_schedule_deadlines() {
+    let mut schedule: Schedule = Schedule::new(Duration::from_millis(10));
+    let start: Instant = schedule.deadline();
+    schedule.wait();
+    // a slow cycle does not shift the following deadlines
+    thread::sleep(Duration::from_millis(15));
+    let jitter: Duration = schedule.wait();
+    assert!(jitter >= Duration::from_millis(5));
+    assert_eq!(start + Duration::from_millis(20), schedule.deadline());
+    schedule.wait();
+    assert!(schedule.elapsed() >= Duration::from_millis(20));
+}
+
+#[test]
+fn test_jitter_stats() {
+    let mut stats: JitterStats = JitterStats::new();
+    assert_eq!(None, stats.avg());
+    stats.add(Duration::from_micros(30));
+    stats.add(Duration::from_micros(10));
+    stats.add(Duration::from_micros(20));
+    assert_eq!(3, stats.count());
+    assert_eq!(Some(Duration::from_micros(10)), stats.min());
+    assert_eq!(Some(Duration::from_micros(20)), stats.avg());
+    assert_eq!(Some(Duration::from_micros(30)), stats.max());
+    assert_eq!("Jitter min/avg/max: 10.0/20.0/30.0 µs", stats.to_string());
+}
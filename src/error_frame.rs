@@ -0,0 +1,181 @@
+//! Decoding of CAN error frames
+//!
+//! Error frames are created by the CAN driver, not sent on the bus, and are
+//! only received if enabled with `CAN_RAW_ERR_FILTER`. The socketcan crate (as
+//! of version 1.7.0) only decodes error frames with a single error class,
+//! while drivers usually report several classes at once, e.g. a controller
+//! problem together with a bus error.
+
+use crate::frame::AnyFrame;
+#[cfg(test)]
+use socketcan::CANFrame;
+use std::fmt;
+
+// error classes in the CAN ID, see linux/can/error.h
+const CAN_ERR_TX_TIMEOUT: u32 = 0x001;
+const CAN_ERR_LOSTARB: u32 = 0x002;
+const CAN_ERR_CRTL: u32 = 0x004;
+const CAN_ERR_PROT: u32 = 0x008;
+const CAN_ERR_TRX: u32 = 0x010;
+const CAN_ERR_ACK: u32 = 0x020;
+const CAN_ERR_BUSOFF: u32 = 0x040;
+const CAN_ERR_BUSERROR: u32 = 0x080;
+const CAN_ERR_RESTARTED: u32 = 0x100;
+
+// controller problems in data byte 1
+const CAN_ERR_CRTL_RX_OVERFLOW: u8 = 0x01;
+const CAN_ERR_CRTL_TX_OVERFLOW: u8 = 0x02;
+const CAN_ERR_CRTL_RX_WARNING: u8 = 0x04;
+const CAN_ERR_CRTL_TX_WARNING: u8 = 0x08;
+const CAN_ERR_CRTL_RX_PASSIVE: u8 = 0x10;
+const CAN_ERR_CRTL_TX_PASSIVE: u8 = 0x20;
+const CAN_ERR_CRTL_ACTIVE: u8 = 0x40;
+
+/// Error or state change of the CAN controller reported by an error frame
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum BusEvent {
+    /// TX timeout of the driver
+    TxTimeout,
+    /// arbitration was lost
+    LostArbitration,
+    /// RX or TX buffer of the controller overflowed
+    BufferOverflow,
+    /// RX or TX error counter reached the warning level
+    ErrorWarning,
+    /// controller became error-passive
+    ErrorPassive,
+    /// controller recovered to error-active
+    ErrorActive,
+    /// protocol violation, e.g. bit stuffing or form error
+    ProtocolViolation,
+    /// transceiver error, e.g. a shorted CAN line
+    TransceiverError,
+    /// no ACK received for a transmitted frame
+    AckError,
+    /// controller went bus-off
+    BusOff,
+    /// bus error
+    BusError,
+    /// controller restarted after bus-off
+    Restarted,
+}
+
+impl BusEvent {
+    /// Name of the event in machine readable reports, e.g. `bus_off`
+    pub fn name(&self) -> &'static str {
+        match self {
+            BusEvent::TxTimeout => "tx_timeout",
+            BusEvent::LostArbitration => "lost_arbitration",
+            BusEvent::BufferOverflow => "buffer_overflow",
+            BusEvent::ErrorWarning => "error_warning",
+            BusEvent::ErrorPassive => "error_passive",
+            BusEvent::ErrorActive => "error_active",
+            BusEvent::ProtocolViolation => "protocol_violation",
+            BusEvent::TransceiverError => "transceiver_error",
+            BusEvent::AckError => "ack_error",
+            BusEvent::BusOff => "bus_off",
+            BusEvent::BusError => "bus_error",
+            BusEvent::Restarted => "restarted",
+        }
+    }
+}
+
+impl fmt::Display for BusEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let description: &str = match self {
+            BusEvent::TxTimeout => "TX timeout",
+            BusEvent::LostArbitration => "lost arbitration",
+            BusEvent::BufferOverflow => "buffer overflow",
+            BusEvent::ErrorWarning => "error warning",
+            BusEvent::ErrorPassive => "error-passive",
+            BusEvent::ErrorActive => "error-active",
+            BusEvent::ProtocolViolation => "protocol violation",
+            BusEvent::TransceiverError => "transceiver error",
+            BusEvent::AckError => "ACK error",
+            BusEvent::BusOff => "bus-off",
+            BusEvent::BusError => "bus error",
+            BusEvent::Restarted => "restarted",
+        };
+        write!(f, "{}", description)
+    }
+}
+
+/// Decodes all events reported by an error frame
+///
+/// Returns an empty list for frames which are no error frames.
+/// # Arguments
+/// * 'frame' - frame received with error frames enabled
+///
+/// # Examples
+/// ```
+/// use can_utils::error_frame::{decode_error_frame, BusEvent};
+/// use socketcan::CANFrame;
+///
+/// // controller problem (TX error-passive) together with a bus error
+/// let frame = CANFrame::new(0x084, &[0, 0x20, 0, 0, 0, 0, 0, 0], false, true).unwrap();
+/// assert_eq!(vec![BusEvent::ErrorPassive, BusEvent::BusError], decode_error_frame(&frame.into()));
+/// ```
+pub fn decode_error_frame(frame: &AnyFrame) -> Vec<BusEvent> {
+    let mut events: Vec<BusEvent> = Vec::new();
+    if !frame.can_id().is_error() {
+        return events;
+    }
+    let class: u32 = frame.id();
+    if class & CAN_ERR_TX_TIMEOUT != 0 {
+        events.push(BusEvent::TxTimeout);
+    }
+    if class & CAN_ERR_LOSTARB != 0 {
+        events.push(BusEvent::LostArbitration);
+    }
+    if class & CAN_ERR_CRTL != 0 {
+        let problem: u8 = frame.data().get(1).copied().unwrap_or(0);
+        if problem & (CAN_ERR_CRTL_RX_OVERFLOW | CAN_ERR_CRTL_TX_OVERFLOW) != 0 {
+            events.push(BusEvent::BufferOverflow);
+        }
+        if problem & (CAN_ERR_CRTL_RX_WARNING | CAN_ERR_CRTL_TX_WARNING) != 0 {
+            events.push(BusEvent::ErrorWarning);
+        }
+        if problem & (CAN_ERR_CRTL_RX_PASSIVE | CAN_ERR_CRTL_TX_PASSIVE) != 0 {
+            events.push(BusEvent::ErrorPassive);
+        }
+        if problem & CAN_ERR_CRTL_ACTIVE != 0 {
+            events.push(BusEvent::ErrorActive);
+        }
+    }
+    if class & CAN_ERR_PROT != 0 {
+        events.push(BusEvent::ProtocolViolation);
+    }
+    if class & CAN_ERR_TRX != 0 {
+        events.push(BusEvent::TransceiverError);
+    }
+    if class & CAN_ERR_ACK != 0 {
+        events.push(BusEvent::AckError);
+    }
+    if class & CAN_ERR_BUSOFF != 0 {
+        events.push(BusEvent::BusOff);
+    }
+    if class & CAN_ERR_BUSERROR != 0 {
+        events.push(BusEvent::BusError);
+    }
+    if class & CAN_ERR_RESTARTED != 0 {
+        events.push(BusEvent::Restarted);
+    }
+    events
+}
+
+#[test]
+fn test_decode_error_frame() {
+    let frame: CANFrame = CANFrame::new(CAN_ERR_ACK | CAN_ERR_BUSOFF | CAN_ERR_RESTARTED, &[], false, true)
+        .unwrap();
+    assert_eq!(vec![BusEvent::AckError, BusEvent::BusOff, BusEvent::Restarted], decode_error_frame(&frame.into()));
+    let frame: CANFrame = CANFrame::new(CAN_ERR_CRTL, &[0, CAN_ERR_CRTL_RX_WARNING | CAN_ERR_CRTL_RX_PASSIVE], false, true)
+        .unwrap();
+    assert_eq!(vec![BusEvent::ErrorWarning, BusEvent::ErrorPassive], decode_error_frame(&frame.into()));
+}
+
+#[test]
+fn test_decode_no_error_frame() {
+    let frame: CANFrame = CANFrame::new(CAN_ERR_BUSOFF, &[], false, false)
+        .unwrap();
+    assert!(decode_error_frame(&frame.into()).is_empty());
+}
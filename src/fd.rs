@@ -0,0 +1,352 @@
+//! CAN FD frames and socket helpers
+//!
+//! The socketcan crate (as of version 1.7.0) only supports classic CAN frames,
+//! so CAN FD frames are read from and written to the file descriptor of a
+//! `CANSocket` directly after enabling `CAN_RAW_FD_FRAMES` on it.
+
+use crate::frame::{new_can_frame, AnyFrame, CAN_MSG_SIZE};
+use crate::id::CanId;
+use socketcan::{CANSocket, ConstructionError, ShouldRetry, EFF_FLAG, EFF_MASK, SFF_MASK};
+use std::io;
+use std::mem::size_of;
+use std::os::unix::io::AsRawFd;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Maximum count of data bytes in a CAN FD frame
+pub const CANFD_MAX_DLEN: usize = 64;
+
+/// Bit rate switch (second bitrate for payload data)
+pub const CANFD_BRS: u8 = libc::CANFD_BRS as u8;
+
+/// Error state indicator of the transmitting node
+pub const CANFD_ESI: u8 = libc::CANFD_ESI as u8;
+
+/// Time to wait before retrying a write rejected with `ENOBUFS`
+///
+/// A full TX queue of the interface does not block `poll`, so the retry is delayed instead.
+const ENOBUFS_BACKOFF: Duration = Duration::from_millis(1);
+
+/// Data lengths a CAN FD frame can carry
+pub const CANFD_VALID_LENGTHS: [usize; 16] = [0, 1, 2, 3, 4, 5, 6, 7, 8, 12, 16, 20, 24, 32, 48, 64];
+
+/// CAN FD frame
+///
+/// Uses the same memory layout as the kernel struct `canfd_frame`.
+#[derive(Debug, Copy, Clone)]
+#[repr(C)]
+pub struct CanFdFrame {
+    /// 32 bit CAN_ID + EFF/RTR/ERR flags
+    can_id: u32,
+    /// data length. Bytes beyond are not valid
+    len: u8,
+    /// additional flags for CAN FD (BRS/ESI)
+    flags: u8,
+    /// reserved
+    res0: u8,
+    /// reserved
+    res1: u8,
+    /// buffer for data
+    data: [u8; CANFD_MAX_DLEN],
+}
+
+/// Returns the smallest valid CAN FD data length which can hold `len` bytes
+///
+/// Returns `None` if `len` exceeds [`CANFD_MAX_DLEN`].
+pub fn padded_len(len: usize) -> Option<usize> {
+    CANFD_VALID_LENGTHS.iter().copied().find(|valid| *valid >= len)
+}
+
+impl CanFdFrame {
+    /// Creates a CAN FD frame, data is padded with zeros to the next valid length
+    /// # Arguments
+    /// * 'id' - CAN ID, IDs above 0x7ff are sent as extended IDs
+    /// * 'data' - at most [`CANFD_MAX_DLEN`] data bytes
+    /// * 'flags' - combination of [`CANFD_BRS`] and [`CANFD_ESI`]
+    pub fn new(id: u32, data: &[u8], flags: u8) -> Result<CanFdFrame, ConstructionError> {
+        CanFdFrame::with_can_id(CanId::new(id)?, data, flags)
+    }
+
+    /// Creates a CAN FD frame with explicitly given frame format
+    /// # Arguments
+    /// * 'can_id' - CAN ID, RTR and ERR flags are not supported by CAN FD
+    /// * 'data' - at most [`CANFD_MAX_DLEN`] data bytes
+    /// * 'flags' - combination of [`CANFD_BRS`] and [`CANFD_ESI`]
+    pub fn with_can_id(can_id: CanId, data: &[u8], flags: u8) -> Result<CanFdFrame, ConstructionError> {
+        let len: usize = match padded_len(data.len()) {
+            Some(len) => len,
+            None => return Err(ConstructionError::TooMuchData),
+        };
+        let mut full_data: [u8; CANFD_MAX_DLEN] = [0; CANFD_MAX_DLEN];
+        full_data[..data.len()].copy_from_slice(data);
+        Ok(CanFdFrame {
+            can_id: can_id.raw(),
+            len: len as u8,
+            flags,
+            res0: 0,
+            res1: 0,
+            data: full_data,
+        })
+    }
+
+    /// Return the actual CAN ID (without EFF/RTR/ERR flags)
+    pub fn id(&self) -> u32 {
+        if self.is_extended() {
+            self.can_id & EFF_MASK
+        } else {
+            self.can_id & SFF_MASK
+        }
+    }
+
+    /// CAN ID including flags
+    pub fn can_id(&self) -> CanId {
+        CanId::from_raw(self.can_id)
+    }
+
+    /// Check if frame uses 29 bit extended frame format
+    pub fn is_extended(&self) -> bool {
+        self.can_id & EFF_FLAG != 0
+    }
+
+    /// CAN FD flags of the frame
+    pub fn flags(&self) -> u8 {
+        self.flags
+    }
+
+    /// Check if the bit rate switch flag is set
+    pub fn is_brs(&self) -> bool {
+        self.flags & CANFD_BRS != 0
+    }
+
+    /// Check if the error state indicator flag is set
+    pub fn is_esi(&self) -> bool {
+        self.flags & CANFD_ESI != 0
+    }
+
+    /// A slice into the actual data (including padding)
+    pub fn data(&self) -> &[u8] {
+        &self.data[..(self.len as usize)]
+    }
+
+    fn empty() -> CanFdFrame {
+        CanFdFrame {
+            can_id: 0,
+            len: 0,
+            flags: 0,
+            res0: 0,
+            res1: 0,
+            data: [0; CANFD_MAX_DLEN],
+        }
+    }
+}
+
+/// Enables (or disables) sending and receiving CAN FD frames on a socket
+/// # Arguments
+/// * 'socket' - opened CAN socket
+/// * 'enable' - `true` to enable CAN FD frames
+pub fn set_fd_frames(socket: &CANSocket, enable: bool) -> io::Result<()> {
+    let value: libc::c_int = enable as libc::c_int;
+    let rv = unsafe {
+        libc::setsockopt(socket.as_raw_fd(),
+                         libc::SOL_CAN_RAW,
+                         libc::CAN_RAW_FD_FRAMES,
+                         &value as *const libc::c_int as *const libc::c_void,
+                         size_of::<libc::c_int>() as libc::socklen_t)
+    };
+    if rv != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Blocking read of a single classic CAN or CAN FD frame
+///
+/// CAN FD frames are only received if enabled with [`set_fd_frames`].
+/// # Arguments
+/// * 'socket' - opened CAN socket
+pub fn read_any_frame(socket: &CANSocket) -> io::Result<AnyFrame> {
+    let mut frame: CanFdFrame = CanFdFrame::empty();
+    let read_rv = unsafe {
+        libc::read(socket.as_raw_fd(),
+                   &mut frame as *mut CanFdFrame as *mut libc::c_void,
+                   size_of::<CanFdFrame>())
+    };
+    if read_rv < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    match read_rv as usize {
+        libc::CAN_MTU => {
+            // first bytes of `canfd_frame` and `can_frame` share the same layout
+            let len: usize = (frame.len as usize).min(CAN_MSG_SIZE);
+            let classic_frame = new_can_frame(frame.can_id(), &frame.data[..len])
+                .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "invalid CAN frame"))?;
+            Ok(AnyFrame::Classic(classic_frame))
+        },
+        libc::CANFD_MTU => Ok(AnyFrame::Fd(frame)),
+        _ => Err(io::Error::new(io::ErrorKind::InvalidData, "incomplete CAN frame")),
+    }
+}
+
+/// Checks the return value of a write of `size` bytes
+///
+/// Must be called directly after the write, so a negative value reports its `errno`.
+/// # Arguments
+/// * 'write_rv' - return value of `write` or `send`
+/// * 'size' - count of bytes to write
+pub(crate) fn check_write(write_rv: isize, size: usize) -> io::Result<()> {
+    if write_rv < 0 {
+        Err(io::Error::last_os_error())
+    } else if write_rv as usize != size {
+        Err(io::Error::new(io::ErrorKind::WriteZero, "short write"))
+    } else {
+        Ok(())
+    }
+}
+
+/// Writes a single CAN FD frame, CAN FD frames must be enabled on the socket
+/// # Arguments
+/// * 'socket' - opened CAN socket
+/// * 'frame' - frame to send
+pub fn write_fd_frame(socket: &CANSocket, frame: &CanFdFrame) -> io::Result<()> {
+    let write_rv = unsafe {
+        libc::write(socket.as_raw_fd(),
+                    frame as *const CanFdFrame as *const libc::c_void,
+                    size_of::<CanFdFrame>())
+    };
+    check_write(write_rv, size_of::<CanFdFrame>())
+}
+
+/// Writes a single CAN FD frame, retrying until it gets sent successfully
+/// # Arguments
+/// * 'socket' - opened CAN socket
+/// * 'frame' - frame to send
+pub fn write_fd_frame_insist(socket: &CANSocket, frame: &CanFdFrame) -> io::Result<()> {
+    loop {
+        match write_fd_frame(socket, frame) {
+            Ok(()) => return Ok(()),
+            Err(e) => {
+                if !e.should_retry() {
+                    return Err(e);
+                }
+            }
+        }
+    }
+}
+
+/// Writes a frame without blocking, the frame has to use the kernel layout
+fn send_dontwait<F>(socket: &CANSocket, frame: &F) -> io::Result<()> {
+    let send_rv = unsafe {
+        libc::send(socket.as_raw_fd(),
+                   frame as *const F as *const libc::c_void,
+                   size_of::<F>(),
+                   libc::MSG_DONTWAIT)
+    };
+    check_write(send_rv, size_of::<F>())
+}
+
+/// Check if a write failed because the socket buffer or the TX queue of the interface is full
+fn is_tx_queue_full(error: &io::Error) -> bool {
+    error.kind() == io::ErrorKind::WouldBlock || error.raw_os_error() == Some(libc::ENOBUFS)
+}
+
+/// Waits until the socket is writable or the timeout expired
+fn poll_writable(socket: &CANSocket, timeout: Duration) -> io::Result<()> {
+    let mut poll_fd: libc::pollfd = libc::pollfd {
+        fd: socket.as_raw_fd(),
+        events: libc::POLLOUT,
+        revents: 0,
+    };
+    // round up, so a remaining timeout below 1 ms does not spin
+    let millis: libc::c_int = ((timeout.as_micros() + 999) / 1000).min(libc::c_int::MAX as u128) as libc::c_int;
+    let poll_rv = unsafe { libc::poll(&mut poll_fd, 1, millis) };
+    if poll_rv < 0 {
+        let error: io::Error = io::Error::last_os_error();
+        if error.kind() != io::ErrorKind::Interrupted {
+            return Err(error);
+        }
+    }
+    Ok(())
+}
+
+/// Writes a single classic CAN or CAN FD frame, waiting at most `timeout` while the TX queue is full
+///
+/// Unlike `write_frame_insist` it does not retry forever, e.g. if no other
+/// node acknowledges the frames and the TX queue fills up. Fails with
+/// `io::ErrorKind::TimedOut` after the timeout. CAN FD frames have to be
+/// enabled with [`set_fd_frames`].
+/// # Arguments
+/// * 'socket' - opened CAN socket
+/// * 'frame' - frame to send
+/// * 'timeout' - maximum time to wait for space in the TX queue
+pub fn write_frame_timeout(socket: &CANSocket, frame: &AnyFrame, timeout: Duration) -> io::Result<()> {
+    let deadline: Instant = Instant::now() + timeout;
+    loop {
+        let send_result: io::Result<()> = match frame {
+            AnyFrame::Classic(frame) => send_dontwait(socket, frame),
+            AnyFrame::Fd(frame) => send_dontwait(socket, frame),
+        };
+        let error: io::Error = match send_result {
+            Ok(()) => return Ok(()),
+            Err(error) => error,
+        };
+        if !is_tx_queue_full(&error) {
+            return Err(error);
+        }
+        let remaining: Duration = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Err(io::Error::new(io::ErrorKind::TimedOut, "bus not acknowledging / TX queue full"));
+        }
+        if error.raw_os_error() == Some(libc::ENOBUFS) {
+            thread::sleep(remaining.min(ENOBUFS_BACKOFF));
+        } else {
+            poll_writable(socket, remaining)?;
+        }
+    }
+}
+
+#[test]
+fn test_fd_frame_layout() {
+    assert_eq!(libc::CANFD_MTU, size_of::<CanFdFrame>());
+}
+
+#[test]
+fn test_padded_len() {
+    assert_eq!(Some(8), padded_len(8));
+    assert_eq!(Some(12), padded_len(9));
+    assert_eq!(Some(48), padded_len(33));
+    assert_eq!(Some(64), padded_len(64));
+    assert_eq!(None, padded_len(65));
+}
+
+#[test]
+fn test_fd_frame_extended() {
+    let frame: CanFdFrame = CanFdFrame::with_can_id(CanId::extended(0x123).unwrap(), &[], 0)
+        .unwrap();
+    assert_eq!(0x123, frame.id());
+    assert!(frame.is_extended());
+}
+
+#[test]
+fn test_fd_frame_padding() {
+    let frame: CanFdFrame = CanFdFrame::new(0x123, &[0xff; 10], CANFD_BRS)
+        .unwrap();
+    assert_eq!(12, frame.data().len());
+    assert_eq!(&[0xff; 10], &frame.data()[..10]);
+    assert_eq!(&[0x00; 2], &frame.data()[10..]);
+    assert!(frame.is_brs());
+    assert!(!frame.is_esi());
+}
+
+#[test]
+fn test_check_write() {
+    assert!(check_write(72, 72).is_ok());
+    assert_eq!(io::ErrorKind::WriteZero, check_write(16, 72).unwrap_err().kind());
+    assert_eq!(io::ErrorKind::WriteZero, check_write(0, 72).unwrap_err().kind());
+}
+
+#[test]
+fn test_tx_queue_full() {
+    assert!(is_tx_queue_full(&io::Error::from_raw_os_error(libc::ENOBUFS)));
+    assert!(is_tx_queue_full(&io::Error::from_raw_os_error(libc::EAGAIN)));
+    assert!(!is_tx_queue_full(&io::Error::from_raw_os_error(libc::ENETDOWN)));
+}
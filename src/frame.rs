@@ -0,0 +1,876 @@
+//! Parsing and formatting of frame strings as used by `cansend`
+//!
+//! A frame string consists of the CAN ID and the data bytes (both in hex)
+//! separated by `#`, e.g. `123#cafe`. Remote frames use `R` (or `r`) instead of
+//! data bytes, optionally followed by the requested DLC, e.g. `123#R` or `123#r5`.
+//! CAN FD frames use `##` followed by a single hex digit for the CAN FD flags
+//! and up to 64 data bytes, e.g. `123##1cafe`.
+//!
+//! Data bytes may be separated by `.` or whitespace for readability, e.g.
+//! `123#ca.fe.ba.be`. Whitespace around the frame string is ignored.
+
+use crate::fd::{CanFdFrame, CANFD_MAX_DLEN};
+use crate::id::CanId;
+use socketcan::{CANFrame, ConstructionError, EFF_FLAG, ERR_FLAG, ERR_MASK, RTR_FLAG};
+use std::error;
+use std::fmt;
+use std::ptr;
+
+/// Maximum count of data bytes in a (classic) CAN frame
+pub const CAN_MSG_SIZE: usize = 8;
+
+/// Count of hex digits of a standard (11 bit) CAN ID
+const CAN_SFF_ID_DIGITS: usize = 3;
+
+/// Count of hex digits of an extended (29 bit) CAN ID
+const CAN_EFF_ID_DIGITS: usize = 8;
+
+/// Creates a classic CAN frame with explicitly given frame format and flags
+///
+/// `socketcan::CANFrame::new` uses the extended frame format only for IDs
+/// above 0x7ff, this function takes the format from the given ID instead.
+/// # Arguments
+/// * 'can_id' - CAN ID including flags
+/// * 'data' - at most [`CAN_MSG_SIZE`] data bytes
+///
+/// # Examples
+/// ```
+/// use can_utils::frame::new_can_frame;
+/// use can_utils::id::CanId;
+///
+/// let frame = new_can_frame(CanId::extended(0x123).unwrap(), &[0xca, 0xfe]).unwrap();
+/// assert!(frame.is_extended());
+/// assert_eq!(0x123, frame.id());
+/// ```
+pub fn new_can_frame(can_id: CanId, data: &[u8]) -> Result<CANFrame, ConstructionError> {
+    let mut frame: CANFrame = CANFrame::new(can_id.id(), data, can_id.is_rtr(), can_id.is_error())?;
+    if frame.is_extended() == can_id.is_extended() {
+        // socketcan chose the same frame format
+        return Ok(frame);
+    }
+    // socketcan 1.7 cannot set the flags of e.g. an extended ID up to 0x7ff, so
+    // only the CAN ID is replaced. `CANFrame` uses the layout of the kernel
+    // struct `can_frame` (which its `write_frame` relies on), i.e. the CAN ID
+    // including flags is its first field.
+    unsafe { ptr::write(&mut frame as *mut CANFrame as *mut u32, can_id.raw()) };
+    // refuse the frame instead of sending a corrupted one if the layout differs
+    let id_matches: bool = frame.err() == can_id.raw() & ERR_MASK
+        && frame.is_extended() == can_id.is_extended()
+        && frame.is_rtr() == can_id.is_rtr()
+        && frame.is_error() == can_id.is_error();
+    if !id_matches || frame.data() != data {
+        return Err(ConstructionError::IDTooLarge);
+    }
+    Ok(frame)
+}
+
+/// Classic CAN or CAN FD frame, e.g. parsed from a frame string
+#[derive(Debug, Copy, Clone)]
+pub enum AnyFrame {
+    /// Classic CAN frame (`<id>#<data>`)
+    Classic(CANFrame),
+    /// CAN FD frame (`<id>##<flags><data>`)
+    Fd(CanFdFrame),
+}
+
+impl AnyFrame {
+    /// Return the actual CAN ID (without EFF/RTR/ERR flags)
+    pub fn id(&self) -> u32 {
+        match self {
+            AnyFrame::Classic(frame) => frame.id(),
+            AnyFrame::Fd(frame) => frame.id(),
+        }
+    }
+
+    /// CAN ID including flags
+    pub fn can_id(&self) -> CanId {
+        match self {
+            AnyFrame::Classic(frame) => {
+                if frame.is_error() {
+                    return CanId::from_raw(ERR_FLAG | frame.err());
+                }
+                let mut raw: u32 = frame.id();
+                if frame.is_extended() {
+                    raw |= EFF_FLAG;
+                }
+                if frame.is_rtr() {
+                    raw |= RTR_FLAG;
+                }
+                CanId::from_raw(raw)
+            },
+            AnyFrame::Fd(frame) => frame.can_id(),
+        }
+    }
+
+    /// Check if frame uses 29 bit extended frame format
+    pub fn is_extended(&self) -> bool {
+        match self {
+            AnyFrame::Classic(frame) => frame.is_extended(),
+            AnyFrame::Fd(frame) => frame.is_extended(),
+        }
+    }
+
+    /// Check if frame is a CAN FD frame
+    pub fn is_fd(&self) -> bool {
+        match self {
+            AnyFrame::Classic(_) => false,
+            AnyFrame::Fd(_) => true,
+        }
+    }
+
+    /// A slice into the actual data
+    pub fn data(&self) -> &[u8] {
+        match self {
+            AnyFrame::Classic(frame) => frame.data(),
+            AnyFrame::Fd(frame) => frame.data(),
+        }
+    }
+
+    /// Creates a frame of the same type with the same ID and flags but other data
+    /// # Arguments
+    /// * 'data' - new data bytes
+    pub fn with_data(&self, data: &[u8]) -> Result<AnyFrame, ConstructionError> {
+        match self {
+            AnyFrame::Classic(_) => new_can_frame(self.can_id(), data).map(AnyFrame::Classic),
+            AnyFrame::Fd(frame) => CanFdFrame::with_can_id(self.can_id(), data, frame.flags()).map(AnyFrame::Fd),
+        }
+    }
+
+    /// Returns the classic CAN frame, `None` for CAN FD frames
+    pub fn classic(self) -> Option<CANFrame> {
+        match self {
+            AnyFrame::Classic(frame) => Some(frame),
+            AnyFrame::Fd(_) => None,
+        }
+    }
+
+    /// Returns the CAN FD frame, `None` for classic CAN frames
+    pub fn fd(self) -> Option<CanFdFrame> {
+        match self {
+            AnyFrame::Classic(_) => None,
+            AnyFrame::Fd(frame) => Some(frame),
+        }
+    }
+}
+
+impl From<CANFrame> for AnyFrame {
+    fn from(frame: CANFrame) -> AnyFrame {
+        AnyFrame::Classic(frame)
+    }
+}
+
+impl From<CanFdFrame> for AnyFrame {
+    fn from(frame: CanFdFrame) -> AnyFrame {
+        AnyFrame::Fd(frame)
+    }
+}
+
+impl fmt::Display for AnyFrame {
+    /// Formats the frame as frame string, see [`frame_to_string`] and [`fd_frame_to_string`]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AnyFrame::Classic(frame) => write!(f, "{}", frame_to_string(frame)),
+            AnyFrame::Fd(frame) => write!(f, "{}", fd_frame_to_string(frame)),
+        }
+    }
+}
+
+/// Error raised while parsing a frame string
+///
+/// Positions are given as character index into the parsed string.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FrameParseError {
+    /// No `#` separating CAN ID and data found
+    MissingSeparator { position: usize },
+    /// No CAN ID in front of the `#`
+    MissingId,
+    /// Character is not a hex digit
+    InvalidHexDigit { position: usize, character: char },
+    /// No flags digit following the `##` of a CAN FD frame
+    MissingFdFlags { position: usize },
+    /// Requested DLC of a remote frame is no single digit from 0 to 8
+    InvalidRemoteDlc { position: usize },
+    /// Separator between the two hex digits of a data byte
+    SplitByte { position: usize },
+    /// Data bytes end with a single hex digit
+    OddNibbleCount { position: usize },
+    /// More data bytes than fit into a frame
+    TooManyBytes { position: usize, max: usize },
+    /// CAN ID exceeds the valid range
+    IdOutOfRange { position: usize },
+    /// CAN ID neither has 3 (standard) nor 8 (extended) digits
+    InvalidIdLength { position: usize },
+    /// CAN ID flags not supported by the frame type, e.g. RTR for CAN FD
+    InvalidIdFlags { position: usize },
+}
+
+impl FrameParseError {
+    /// Position of the offending character
+    pub fn position(&self) -> usize {
+        match *self {
+            FrameParseError::MissingSeparator { position } => position,
+            FrameParseError::MissingId => 0,
+            FrameParseError::InvalidHexDigit { position, .. } => position,
+            FrameParseError::MissingFdFlags { position } => position,
+            FrameParseError::InvalidRemoteDlc { position } => position,
+            FrameParseError::SplitByte { position } => position,
+            FrameParseError::OddNibbleCount { position } => position,
+            FrameParseError::TooManyBytes { position, .. } => position,
+            FrameParseError::IdOutOfRange { position } => position,
+            FrameParseError::InvalidIdLength { position } => position,
+            FrameParseError::InvalidIdFlags { position } => position,
+        }
+    }
+
+    /// Error message with a caret pointing at the offending character
+    /// # Arguments
+    /// * 'input' - string which was parsed
+    ///
+    /// # Examples
+    /// ```
+    /// use can_utils::frame::parse_frame_string;
+    ///
+    /// let error = parse_frame_string("123#zz").unwrap_err();
+    /// assert_eq!("123#zz\n    ^ invalid hex digit 'z'", error.annotate("123#zz"));
+    /// ```
+    pub fn annotate(&self, input: &str) -> String {
+        format!("{}\n{}^ {}", input, " ".repeat(self.position()), self)
+    }
+
+    fn shift(self, offset: usize) -> FrameParseError {
+        match self {
+            FrameParseError::MissingSeparator { position } =>
+                FrameParseError::MissingSeparator { position: position + offset },
+            FrameParseError::MissingId => FrameParseError::MissingId,
+            FrameParseError::InvalidHexDigit { position, character } =>
+                FrameParseError::InvalidHexDigit { position: position + offset, character },
+            FrameParseError::MissingFdFlags { position } =>
+                FrameParseError::MissingFdFlags { position: position + offset },
+            FrameParseError::InvalidRemoteDlc { position } =>
+                FrameParseError::InvalidRemoteDlc { position: position + offset },
+            FrameParseError::SplitByte { position } =>
+                FrameParseError::SplitByte { position: position + offset },
+            FrameParseError::OddNibbleCount { position } =>
+                FrameParseError::OddNibbleCount { position: position + offset },
+            FrameParseError::TooManyBytes { position, max } =>
+                FrameParseError::TooManyBytes { position: position + offset, max },
+            FrameParseError::IdOutOfRange { position } =>
+                FrameParseError::IdOutOfRange { position: position + offset },
+            FrameParseError::InvalidIdLength { position } =>
+                FrameParseError::InvalidIdLength { position: position + offset },
+            FrameParseError::InvalidIdFlags { position } =>
+                FrameParseError::InvalidIdFlags { position: position + offset },
+        }
+    }
+}
+
+impl fmt::Display for FrameParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FrameParseError::MissingSeparator { .. } => write!(f, "missing '#' between ID and data"),
+            FrameParseError::MissingId => write!(f, "missing CAN ID"),
+            FrameParseError::InvalidHexDigit { character, .. } => write!(f, "invalid hex digit '{}'", character),
+            FrameParseError::MissingFdFlags { .. } => write!(f, "missing CAN FD flags digit after '##'"),
+            FrameParseError::InvalidRemoteDlc { .. } =>
+                write!(f, "remote frame DLC must be a single digit from 0 to {}", CAN_MSG_SIZE),
+            FrameParseError::SplitByte { .. } => write!(f, "separator splits a data byte"),
+            FrameParseError::OddNibbleCount { .. } => write!(f, "odd count of hex digits, data byte incomplete"),
+            FrameParseError::TooManyBytes { max, .. } => write!(f, "too many data bytes (at most {})", max),
+            FrameParseError::IdOutOfRange { .. } => write!(f, "CAN ID out of range"),
+            FrameParseError::InvalidIdLength { .. } =>
+                write!(f, "CAN ID needs 3 (standard) or 8 (extended) hex digits"),
+            FrameParseError::InvalidIdFlags { .. } => write!(f, "CAN ID flags not supported by frame type"),
+        }
+    }
+}
+
+impl error::Error for FrameParseError {}
+
+fn hex_nibble(character: char, position: usize) -> Result<u8, FrameParseError> {
+    match character.to_digit(16) {
+        Some(nibble) => Ok(nibble as u8),
+        None => Err(FrameParseError::InvalidHexDigit { position, character }),
+    }
+}
+
+/// Parses a string of hex digits into data bytes
+/// # Arguments
+/// * 'input' - data bytes as hex string (two digits per byte), bytes may be
+///   separated by `.` or whitespace
+///
+/// Fails if a character is no hex digit, a separator splits a byte, the count
+/// of digits is odd or exceeds [`CAN_MSG_SIZE`] bytes.
+///
+/// # Examples
+/// ```
+/// use can_utils::frame::data_string_to_hex;
+///
+/// assert_eq!(vec![0xca, 0xfe, 0xba, 0xbe], data_string_to_hex("ca.fe.ba.be").unwrap());
+/// assert!(data_string_to_hex("c.afe").is_err());
+/// ```
+pub fn data_string_to_hex(input: &str) -> Result<Vec<u8>, FrameParseError> {
+    parse_data_bytes(input, CAN_MSG_SIZE)
+}
+
+fn is_data_separator(character: char) -> bool {
+    character == '.' || character.is_whitespace()
+}
+
+fn parse_data_bytes(input: &str, max: usize) -> Result<Vec<u8>, FrameParseError> {
+    let mut result: Vec<u8> = Vec::with_capacity(max);
+    // high nibble of the current byte and its position
+    let mut high: Option<(u8, usize)> = None;
+    for (position, character) in input.chars().enumerate() {
+        if is_data_separator(character) {
+            if high.is_some() {
+                return Err(FrameParseError::SplitByte { position });
+            }
+            continue;
+        }
+        match high.take() {
+            None => {
+                if result.len() == max {
+                    return Err(FrameParseError::TooManyBytes { position, max });
+                }
+                high = Some((hex_nibble(character, position)?, position));
+            },
+            Some((nibble, _)) => result.push(nibble << 4 | hex_nibble(character, position)?),
+        }
+    }
+    if let Some((_, position)) = high {
+        return Err(FrameParseError::OddNibbleCount { position });
+    }
+    Ok(result)
+}
+
+/// Parses the requested DLC following the `R` of a remote frame
+/// # Arguments
+/// * 'input' - characters following the `R`, empty for DLC 0
+fn remote_dlc(input: &str) -> Result<usize, FrameParseError> {
+    let mut characters = input.chars();
+    let dlc: usize = match characters.next() {
+        None => return Ok(0),
+        Some(character) => match character.to_digit(10) {
+            Some(dlc) if dlc as usize <= CAN_MSG_SIZE => dlc as usize,
+            _ => return Err(FrameParseError::InvalidRemoteDlc { position: 0 }),
+        },
+    };
+    if characters.next().is_some() {
+        return Err(FrameParseError::InvalidRemoteDlc { position: 1 });
+    }
+    Ok(dlc)
+}
+
+/// Parses a CAN ID given as hex string
+///
+/// 3 digits give a standard (11 bit) ID, 8 digits an extended (29 bit) ID.
+/// The 8 digits are the raw value including flags (as `canid_t`): `RTR_FLAG`
+/// (0x40000000) marks a remote frame, `ERR_FLAG` (0x20000000) an error frame,
+/// the `EFF_FLAG` is set implicitly for all other frames.
+/// # Arguments
+/// * 'input' - CAN ID as hex string (3 or 8 digits)
+///
+/// # Examples
+/// ```
+/// use can_utils::frame::id_string_to_hex;
+///
+/// assert!(!id_string_to_hex("123").unwrap().is_extended());
+/// assert!(id_string_to_hex("00000123").unwrap().is_extended());
+/// assert!(id_string_to_hex("40000123").unwrap().is_rtr());
+/// ```
+pub fn id_string_to_hex(input: &str) -> Result<CanId, FrameParseError> {
+    if input.is_empty() {
+        return Err(FrameParseError::MissingId);
+    }
+    let mut value: u32 = 0;
+    for (position, character) in input.chars().enumerate() {
+        if position == CAN_EFF_ID_DIGITS {
+            return Err(FrameParseError::InvalidIdLength { position });
+        }
+        value = value << 4 | hex_nibble(character, position)? as u32;
+    }
+    let result: CanId = match input.chars().count() {
+        CAN_SFF_ID_DIGITS => CanId::standard(value)
+            .map_err(|_| FrameParseError::IdOutOfRange { position: 0 })?,
+        CAN_EFF_ID_DIGITS => {
+            if value & ERR_FLAG == 0 {
+                CanId::from_raw(value | EFF_FLAG)
+            } else if value & (EFF_FLAG | RTR_FLAG) == 0 {
+                CanId::from_raw(value)
+            } else {
+                // error frames are neither extended nor remote frames
+                return Err(FrameParseError::IdOutOfRange { position: 0 });
+            }
+        },
+        len => return Err(FrameParseError::InvalidIdLength { position: len.min(CAN_SFF_ID_DIGITS) }),
+    };
+    log::debug!("Result id parsing: {:x}", result.raw());
+    Ok(result)
+}
+
+/// Parses a frame string into a classic CAN or CAN FD frame
+/// # Arguments
+/// * 'frame_string' - frame as `<id>#<data>`, `<id>#R[<dlc>]` or `<id>##<flags><data>`
+///
+/// # Examples
+/// ```
+/// use can_utils::frame::parse_frame_string;
+///
+/// let frame = parse_frame_string("444#R").unwrap().classic().unwrap();
+/// assert!(frame.is_rtr());
+///
+/// let fd_frame = parse_frame_string("123##1cafe").unwrap().fd().unwrap();
+/// assert!(fd_frame.is_brs());
+/// ```
+pub fn parse_frame_string(frame_string: &str) -> Result<AnyFrame, FrameParseError> {
+    let trimmed: &str = frame_string.trim_start();
+    let leading: usize = frame_string.chars().count() - trimmed.chars().count();
+    parse_trimmed_frame_string(trimmed.trim_end())
+        .map_err(|e| e.shift(leading))
+}
+
+/// Parses a line of a frame list, e.g. read from stdin
+///
+/// Blank lines and comments (lines starting with `#`) contain no frame.
+/// Error positions refer to the untrimmed line.
+/// # Arguments
+/// * 'line' - single line without line break
+///
+/// # Examples
+/// ```
+/// use can_utils::frame::parse_frame_line;
+///
+/// assert!(parse_frame_line("  # comment").unwrap().is_none());
+/// assert_eq!(0x123, parse_frame_line(" 123#cafe").unwrap().unwrap().id());
+/// ```
+pub fn parse_frame_line(line: &str) -> Result<Option<AnyFrame>, FrameParseError> {
+    let trimmed: &str = line.trim();
+    if trimmed.is_empty() || trimmed.starts_with('#') {
+        return Ok(None);
+    }
+    parse_frame_string(line).map(Some)
+}
+
+fn parse_trimmed_frame_string(frame_string: &str) -> Result<AnyFrame, FrameParseError> {
+    let frame_tokens: Vec<&str> = frame_string
+        .splitn(2, '#')
+        .collect();
+    log::debug!("Frame tokens: {:?}", frame_tokens);
+    if frame_tokens.len() != 2 {
+        return Err(FrameParseError::MissingSeparator { position: frame_string.chars().count() });
+    }
+    let frame_id: CanId = id_string_to_hex(frame_tokens[0])?;
+    let frame_data: &str = frame_tokens[1];
+    let data_offset: usize = frame_tokens[0].chars().count() + 1;
+    if let Some(fd_data) = frame_data.strip_prefix('#') {
+        if frame_id.is_rtr() || frame_id.is_error() {
+            return Err(FrameParseError::InvalidIdFlags { position: 0 });
+        }
+        let mut fd_characters = fd_data.chars();
+        let flags: u8 = match fd_characters.next() {
+            Some(character) => hex_nibble(character, data_offset + 1)?,
+            None => return Err(FrameParseError::MissingFdFlags { position: data_offset + 1 }),
+        };
+        let data_bytes: Vec<u8> = parse_data_bytes(fd_characters.as_str(), CANFD_MAX_DLEN)
+            .map_err(|e| e.shift(data_offset + 2))?;
+        log::debug!("FD flags: {:x}, frame bytes: {:x?}", flags, data_bytes);
+        let frame: CanFdFrame =
+            CanFdFrame::with_can_id(frame_id, &data_bytes, flags)
+                .expect("Error creating CAN-FD-Frame!");
+        Ok(AnyFrame::Fd(frame))
+    } else if let Some(dlc_string) = frame_data.strip_prefix(['R', 'r']) {
+        // set RTR flag in frame, the data length is the requested DLC
+        let dlc: usize = remote_dlc(dlc_string)
+            .map_err(|e| e.shift(data_offset + 1))?;
+        let frame: CANFrame =
+            new_can_frame(frame_id.with_rtr(), &[0; CAN_MSG_SIZE][..dlc])
+                .expect("Error creating CAN-Remote-Frame");
+        Ok(AnyFrame::Classic(frame))
+    } else {
+        let data_bytes: Vec<u8> = data_string_to_hex(frame_data)
+            .map_err(|e| e.shift(data_offset))?;
+        log::debug!("Frame bytes: {:x?}", data_bytes);
+        let frame: CANFrame =
+            new_can_frame(frame_id, &data_bytes)
+                .expect("Error creating CAN-Frame!");
+        Ok(AnyFrame::Classic(frame))
+    }
+}
+
+/// Formats a CAN frame as frame string, i.e. the inverse of [`parse_frame_string`]
+///
+/// Standard IDs are printed with 3 digits, extended IDs and error frames with 8 digits.
+/// # Arguments
+/// * 'frame' - CAN frame to format
+pub fn frame_to_string(frame: &CANFrame) -> String {
+    let id_string: String = if frame.is_error() {
+        format!("{:08X}", ERR_FLAG | frame.err())
+    } else if frame.is_extended() {
+        format!("{:08X}", frame.id())
+    } else {
+        format!("{:03X}", frame.id())
+    };
+    let data_string: String = if frame.is_rtr() && frame.data().is_empty() {
+        "R".to_owned()
+    } else if frame.is_rtr() {
+        format!("R{}", frame.data().len())
+    } else {
+        frame.data()
+            .iter()
+            .map(|b| format!("{:02X}", b))
+            .collect()
+    };
+    format!("{}#{}", id_string, data_string)
+}
+
+/// Formats a CAN FD frame as frame string (`<id>##<flags><data>`)
+/// # Arguments
+/// * 'frame' - CAN FD frame to format
+pub fn fd_frame_to_string(frame: &CanFdFrame) -> String {
+    let id_string: String = if frame.is_extended() {
+        format!("{:08X}", frame.id())
+    } else {
+        format!("{:03X}", frame.id())
+    };
+    let data_string: String = frame.data()
+        .iter()
+        .map(|b| format!("{:02X}", b))
+        .collect();
+    format!("{}##{:X}{}", id_string, frame.flags(), data_string)
+}
+
+#[test]
+fn test_frame_parsing() {
+    let test_frame: &str = "123#cafe";
+    let exptected_frame: CANFrame = CANFrame::new(0x123, &[0xca, 0xfe], false, false)
+        .unwrap();
+    let created_frame: CANFrame = parse_frame_string(test_frame)
+        .unwrap()
+        .classic()
+        .unwrap();
+    assert_eq!(exptected_frame.id(), created_frame.id());
+    assert_eq!(exptected_frame.is_extended(), created_frame.is_extended());
+    assert_eq!(exptected_frame.is_rtr(), created_frame.is_rtr());
+    for i in 0..exptected_frame.data().len() {
+        assert_eq!(exptected_frame.data()[i], created_frame.data()[i]);
+    }
+}
+
+#[test]
+fn test_frame_parsing_remote() {
+    let test_frame: &str = "444#R";
+    let exptected_frame: CANFrame = CANFrame::new(0x444, &[], true, false)
+        .unwrap();
+    let created_frame: CANFrame = parse_frame_string(test_frame)
+        .unwrap()
+        .classic()
+        .unwrap();
+    assert_eq!(exptected_frame.id(), created_frame.id());
+    assert_eq!(exptected_frame.is_rtr(), created_frame.is_rtr());
+    assert_eq!(exptected_frame.data().len(), created_frame.data().len());
+}
+
+#[test]
+fn test_frame_parsing_remote_dlc() {
+    let created_frame: CANFrame = parse_frame_string("444#R5")
+        .unwrap()
+        .classic()
+        .unwrap();
+    assert_eq!(0x444, created_frame.id());
+    assert!(created_frame.is_rtr());
+    assert_eq!(5, created_frame.data().len());
+    let created_frame: CANFrame = parse_frame_string("00000444#R8")
+        .unwrap()
+        .classic()
+        .unwrap();
+    assert!(created_frame.is_extended());
+    assert!(created_frame.is_rtr());
+    assert_eq!(8, created_frame.data().len());
+}
+
+#[test]
+fn test_frame_parsing_remote_lowercase() {
+    let created_frame: CANFrame = parse_frame_string("444#r")
+        .unwrap()
+        .classic()
+        .unwrap();
+    assert_eq!(0x444, created_frame.id());
+    assert!(created_frame.is_rtr());
+    assert_eq!(0, created_frame.data().len());
+    let created_frame: CANFrame = parse_frame_string("444#r5")
+        .unwrap()
+        .classic()
+        .unwrap();
+    assert!(created_frame.is_rtr());
+    assert_eq!(5, created_frame.data().len());
+    assert_eq!(FrameParseError::InvalidRemoteDlc { position: 5 },
+        parse_frame_string("444#r9").unwrap_err());
+}
+
+#[test]
+fn test_frame_parsing_remote_invalid_dlc() {
+    assert_eq!(FrameParseError::InvalidRemoteDlc { position: 5 },
+        parse_frame_string("444#R9").unwrap_err());
+    assert_eq!(FrameParseError::InvalidRemoteDlc { position: 5 },
+        parse_frame_string("444#Rx").unwrap_err());
+    assert_eq!(FrameParseError::InvalidRemoteDlc { position: 6 },
+        parse_frame_string("444#R12").unwrap_err());
+    assert_eq!(FrameParseError::InvalidHexDigit { position: 5, character: 'R' },
+        parse_frame_string("444#cR").unwrap_err());
+}
+
+#[test]
+fn test_frame_parsing_extended() {
+    let test_frame: &str = "00111fff#0102030405060708";
+    let exptected_frame: CANFrame = CANFrame::new(0x111fff, &[0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08], false, false)
+        .unwrap();
+    let created_frame: CANFrame = parse_frame_string(test_frame)
+        .unwrap()
+        .classic()
+        .unwrap();
+    assert_eq!(exptected_frame.id(), created_frame.id());
+    assert_eq!(exptected_frame.is_extended(), created_frame.is_extended());
+    assert!(created_frame.is_extended());
+    assert_eq!(exptected_frame.is_rtr(), created_frame.is_rtr());
+    for i in 0..exptected_frame.data().len() {
+        assert_eq!(exptected_frame.data()[i], created_frame.data()[i]);
+    }
+}
+
+#[test]
+fn test_frame_parsing_extended_small_id() {
+    let created_frame: CANFrame = parse_frame_string("00000123#cafe")
+        .unwrap()
+        .classic()
+        .unwrap();
+    assert_eq!(0x123, created_frame.id());
+    assert!(created_frame.is_extended());
+    assert_eq!(&[0xca, 0xfe], created_frame.data());
+}
+
+#[test]
+fn test_id_parsing_standard() {
+    let frame_id: CanId = id_string_to_hex("7ff").unwrap();
+    assert_eq!(0x7ff, frame_id.id());
+    assert!(!frame_id.is_extended());
+}
+
+#[test]
+fn test_id_parsing_extended() {
+    let frame_id: CanId = id_string_to_hex("1ff7123a").unwrap();
+    assert_eq!(0x1ff7123a, frame_id.id());
+    assert!(frame_id.is_extended());
+    let frame_id: CanId = id_string_to_hex("00000001").unwrap();
+    assert_eq!(0x1, frame_id.id());
+    assert!(frame_id.is_extended());
+}
+
+#[test]
+fn test_id_parsing_flags() {
+    let frame_id: CanId = id_string_to_hex("c0000123").unwrap();
+    assert!(frame_id.is_extended());
+    assert!(frame_id.is_rtr());
+    let frame_id: CanId = id_string_to_hex("20000004").unwrap();
+    assert!(frame_id.is_error());
+    assert!(!frame_id.is_extended());
+    assert_eq!(0x4, frame_id.id());
+}
+
+#[test]
+fn test_id_parsing_invalid_length() {
+    assert_eq!(Err(FrameParseError::InvalidIdLength { position: 2 }), id_string_to_hex("12"));
+    assert_eq!(Err(FrameParseError::InvalidIdLength { position: 3 }), id_string_to_hex("111fff"));
+    assert_eq!(Err(FrameParseError::InvalidIdLength { position: 8 }), id_string_to_hex("000000123"));
+}
+
+#[test]
+fn test_frame_parsing_invalid_digit() {
+    assert_eq!(FrameParseError::InvalidHexDigit { position: 4, character: 'z' },
+        parse_frame_string("123#zz").unwrap_err());
+    assert_eq!(FrameParseError::InvalidHexDigit { position: 1, character: 'x' },
+        parse_frame_string("1x3#cafe").unwrap_err());
+}
+
+#[test]
+fn test_frame_parsing_odd_nibbles() {
+    assert_eq!(FrameParseError::OddNibbleCount { position: 8 },
+        parse_frame_string("123#cafe1").unwrap_err());
+}
+
+#[test]
+fn test_frame_parsing_too_many_bytes() {
+    assert_eq!(FrameParseError::TooManyBytes { position: 20, max: CAN_MSG_SIZE },
+        parse_frame_string("123#010203040506070809").unwrap_err());
+}
+
+#[test]
+fn test_frame_parsing_missing_separator() {
+    assert_eq!(FrameParseError::MissingSeparator { position: 7 },
+        parse_frame_string("123cafe").unwrap_err());
+    assert_eq!(FrameParseError::MissingId, parse_frame_string("#cafe").unwrap_err());
+}
+
+#[test]
+fn test_frame_parsing_separators() {
+    let created_frame: CANFrame = parse_frame_string("123#ca.fe.ba.be")
+        .unwrap()
+        .classic()
+        .unwrap();
+    assert_eq!(&[0xca, 0xfe, 0xba, 0xbe], created_frame.data());
+    let created_frame: CANFrame = parse_frame_string("  123#11 22\t33.  ")
+        .unwrap()
+        .classic()
+        .unwrap();
+    assert_eq!(&[0x11, 0x22, 0x33], created_frame.data());
+    let created_frame: CanFdFrame = parse_frame_string("123##0.11.22.33")
+        .unwrap()
+        .fd()
+        .unwrap();
+    assert_eq!(&[0x11, 0x22, 0x33], created_frame.data());
+}
+
+#[test]
+fn test_frame_parsing_split_byte() {
+    assert_eq!(FrameParseError::SplitByte { position: 5 },
+        parse_frame_string("123#c.afe").unwrap_err());
+    assert_eq!(FrameParseError::SplitByte { position: 7 },
+        parse_frame_string("  123#c afe").unwrap_err());
+    assert_eq!(FrameParseError::InvalidHexDigit { position: 4, character: ' ' },
+        parse_frame_string(" 123 #cafe").unwrap_err());
+}
+
+#[test]
+fn test_id_parsing_out_of_range() {
+    assert_eq!(Err(FrameParseError::IdOutOfRange { position: 0 }), id_string_to_hex("800"));
+    assert_eq!(Err(FrameParseError::IdOutOfRange { position: 0 }), id_string_to_hex("a0000001"));
+}
+
+#[test]
+fn test_parse_error_annotation() {
+    let error: FrameParseError = parse_frame_string("123#ca-e").unwrap_err();
+    assert_eq!("123#ca-e\n      ^ invalid hex digit '-'", error.annotate("123#ca-e"));
+}
+
+#[test]
+fn test_fd_frame_parsing() {
+    let created_frame: CanFdFrame = parse_frame_string("123##3cafe")
+        .unwrap()
+        .fd()
+        .unwrap();
+    assert_eq!(0x123, created_frame.id());
+    assert!(created_frame.is_brs());
+    assert!(created_frame.is_esi());
+    assert_eq!(&[0xca, 0xfe], created_frame.data());
+}
+
+#[test]
+fn test_fd_frame_parsing_padding() {
+    let data_string: String = "11".repeat(33);
+    let created_frame: CanFdFrame = parse_frame_string(&format!("123##0{}", data_string))
+        .unwrap()
+        .fd()
+        .unwrap();
+    assert_eq!(48, created_frame.data().len());
+    assert_eq!(&[0x11; 33], &created_frame.data()[..33]);
+    assert_eq!(&[0x00; 15], &created_frame.data()[33..]);
+}
+
+#[test]
+fn test_fd_frame_parsing_errors() {
+    assert_eq!(FrameParseError::MissingFdFlags { position: 5 },
+        parse_frame_string("123##").unwrap_err());
+    assert_eq!(FrameParseError::InvalidHexDigit { position: 5, character: 'R' },
+        parse_frame_string("123##R").unwrap_err());
+    assert_eq!(FrameParseError::InvalidIdFlags { position: 0 },
+        parse_frame_string("40000123##1cafe").unwrap_err());
+    let data_string: String = "11".repeat(65);
+    assert_eq!(FrameParseError::TooManyBytes { position: 134, max: CANFD_MAX_DLEN },
+        parse_frame_string(&format!("123##0{}", data_string)).unwrap_err());
+}
+
+#[test]
+fn test_fd_frame_formatting() {
+    let frame: CanFdFrame = CanFdFrame::new(0x123, &[0xca, 0xfe], 1)
+        .unwrap();
+    assert_eq!("123##1CAFE", fd_frame_to_string(&frame));
+}
+
+#[test]
+fn test_frame_formatting() {
+    let frame: CANFrame = CANFrame::new(0x123, &[0xca, 0xfe], false, false)
+        .unwrap();
+    assert_eq!("123#CAFE", frame_to_string(&frame));
+    let remote_frame: CANFrame = CANFrame::new(0x1ff7123, &[], true, false)
+        .unwrap();
+    assert_eq!("01FF7123#R", frame_to_string(&remote_frame));
+    let remote_dlc_frame: CANFrame = CANFrame::new(0x123, &[0; 3], true, false)
+        .unwrap();
+    assert_eq!("123#R3", frame_to_string(&remote_dlc_frame));
+    let extended_frame: CANFrame = new_can_frame(CanId::extended(0x123).unwrap(), &[0x01])
+        .unwrap();
+    assert_eq!("00000123#01", frame_to_string(&extended_frame));
+}
+
+#[test]
+fn test_any_frame_can_id() {
+    let extended: AnyFrame = parse_frame_string("00000123#cafe").unwrap();
+    assert_eq!(CanId::extended(0x123).unwrap(), extended.can_id());
+    let remote: AnyFrame = parse_frame_string("123#R").unwrap();
+    assert_eq!(CanId::standard(0x123).unwrap().with_rtr(), remote.can_id());
+    let fd: AnyFrame = parse_frame_string("123##1cafe").unwrap();
+    assert_eq!(CanId::standard(0x123).unwrap(), fd.can_id());
+}
+
+#[test]
+fn test_parse_frame_line() {
+    assert!(parse_frame_line("").unwrap().is_none());
+    assert!(parse_frame_line("   ").unwrap().is_none());
+    assert!(parse_frame_line("#123#cafe").unwrap().is_none());
+    let frame: AnyFrame = parse_frame_line("123##1cafe\r").unwrap().unwrap();
+    assert!(frame.is_fd());
+    assert_eq!(FrameParseError::InvalidHexDigit { position: 6, character: 'z' },
+        parse_frame_line("  123#zz").unwrap_err());
+}
+
+#[test]
+fn test_any_frame_with_data() {
+    let frame: AnyFrame = parse_frame_string("12345678#01").unwrap();
+    let changed: AnyFrame = frame.with_data(&[0x02, 0x03]).unwrap();
+    assert_eq!(frame.can_id(), changed.can_id());
+    assert_eq!(&[0x02, 0x03], changed.data());
+    let fd_frame: AnyFrame = parse_frame_string("123##1cafe").unwrap();
+    let changed: CanFdFrame = fd_frame.with_data(&[0; 9]).unwrap().fd().unwrap();
+    assert!(changed.is_brs());
+    assert_eq!(12, changed.data().len());
+}
+
+#[test]
+fn test_new_can_frame_round_trip() {
+    let ids: [u32; 4] = [0x0, 0x123, 0x7ff, 0x1abcd000];
+    for &id in ids.iter() {
+        for &(rtr, err) in [(false, false), (true, false), (false, true)].iter() {
+            let expected: CANFrame = CANFrame::new(id, &[0xca, 0xfe], rtr, err).unwrap();
+            let mut can_id: CanId = CanId::new(id).unwrap();
+            if rtr {
+                can_id = can_id.with_rtr();
+            }
+            if err {
+                can_id = CanId::from_raw(can_id.raw() | ERR_FLAG);
+            }
+            let frame: CANFrame = new_can_frame(can_id, &[0xca, 0xfe]).unwrap();
+            assert_eq!(expected.id(), frame.id());
+            assert_eq!(expected.data(), frame.data());
+            assert_eq!(expected.is_extended(), frame.is_extended());
+            assert_eq!(expected.is_rtr(), frame.is_rtr());
+            assert_eq!(expected.is_error(), frame.is_error());
+        }
+    }
+    // extended ID up to 0x7ff gets its flags after creating the frame
+    let frame: CANFrame = new_can_frame(CanId::extended(0x7ff).unwrap().with_rtr(), &[1, 2, 3]).unwrap();
+    assert_eq!(0x7ff, frame.id());
+    assert_eq!(&[1, 2, 3], frame.data());
+    assert!(frame.is_extended());
+    assert!(frame.is_rtr());
+    assert!(!frame.is_error());
+    assert!(new_can_frame(CanId::extended(0x1).unwrap(), &[0; CAN_MSG_SIZE + 1]).is_err());
+}
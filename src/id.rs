@@ -0,0 +1,109 @@
+//! CAN IDs including the EFF/RTR/ERR flag bits
+//!
+//! The socketcan crate (as of version 1.7.0) decides between standard and
+//! extended IDs by the value of the ID. [`CanId`] allows to state the frame
+//! format explicitly, e.g. an extended ID with a value below 0x800.
+
+use socketcan::{ConstructionError, EFF_FLAG, EFF_MASK, ERR_FLAG, ERR_MASK, RTR_FLAG, SFF_MASK};
+
+/// CAN ID with EFF/RTR/ERR flags, i.e. the kernel type `canid_t`
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct CanId {
+    raw: u32,
+}
+
+impl CanId {
+    /// Creates a CAN ID, IDs above 0x7ff are extended IDs
+    /// # Arguments
+    /// * 'id' - CAN ID without flags
+    pub fn new(id: u32) -> Result<CanId, ConstructionError> {
+        if id > SFF_MASK {
+            CanId::extended(id)
+        } else {
+            CanId::standard(id)
+        }
+    }
+
+    /// Creates an 11 bit standard CAN ID
+    /// # Arguments
+    /// * 'id' - CAN ID, at most 0x7ff
+    pub fn standard(id: u32) -> Result<CanId, ConstructionError> {
+        if id > SFF_MASK {
+            return Err(ConstructionError::IDTooLarge);
+        }
+        Ok(CanId { raw: id })
+    }
+
+    /// Creates a 29 bit extended CAN ID
+    /// # Arguments
+    /// * 'id' - CAN ID, at most 0x1fffffff
+    pub fn extended(id: u32) -> Result<CanId, ConstructionError> {
+        if id > EFF_MASK {
+            return Err(ConstructionError::IDTooLarge);
+        }
+        Ok(CanId { raw: id | EFF_FLAG })
+    }
+
+    /// Creates a CAN ID from its raw value including flags
+    /// # Arguments
+    /// * 'raw' - CAN ID combined with `EFF_FLAG`, `RTR_FLAG` and `ERR_FLAG`
+    ///
+    /// # Examples
+    /// ```
+    /// use can_utils::id::CanId;
+    /// use socketcan::{EFF_FLAG, RTR_FLAG};
+    ///
+    /// let id = CanId::from_raw(EFF_FLAG | RTR_FLAG | 0x123);
+    /// assert!(id.is_extended());
+    /// assert!(id.is_rtr());
+    /// assert_eq!(0x123, id.id());
+    /// ```
+    pub fn from_raw(raw: u32) -> CanId {
+        CanId { raw }
+    }
+
+    /// Sets the RTR flag
+    pub fn with_rtr(self) -> CanId {
+        CanId { raw: self.raw | RTR_FLAG }
+    }
+
+    /// Raw value including flags
+    pub fn raw(&self) -> u32 {
+        self.raw
+    }
+
+    /// Return the actual CAN ID (without EFF/RTR/ERR flags)
+    pub fn id(&self) -> u32 {
+        if self.is_error() {
+            self.raw & ERR_MASK
+        } else if self.is_extended() {
+            self.raw & EFF_MASK
+        } else {
+            self.raw & SFF_MASK
+        }
+    }
+
+    /// Check if ID uses 29 bit extended frame format
+    pub fn is_extended(&self) -> bool {
+        self.raw & EFF_FLAG != 0
+    }
+
+    /// Check if ID is a remote transmission request
+    pub fn is_rtr(&self) -> bool {
+        self.raw & RTR_FLAG != 0
+    }
+
+    /// Check if ID belongs to an error frame
+    pub fn is_error(&self) -> bool {
+        self.raw & ERR_FLAG != 0
+    }
+}
+
+#[test]
+fn test_can_id_format() {
+    assert!(!CanId::new(0x7ff).unwrap().is_extended());
+    assert!(CanId::new(0x800).unwrap().is_extended());
+    assert!(CanId::extended(0x123).unwrap().is_extended());
+    assert!(CanId::standard(0x800).is_err());
+    assert!(CanId::extended(0x20000000).is_err());
+}
@@ -0,0 +1,33 @@
+//! Rusty implementation of famous can-utils (for learning purposes)
+//!
+//! Besides the `cansend` and `canfdtest` binaries the crate exposes the logic
+//! they are built on, so it can be reused e.g. in own test harnesses:
+//!
+//! * [`frame`] - parsing and formatting of cansend frame strings (`123#cafe`)
+//! * [`id`] - CAN IDs with explicit frame format and flags
+//! * [`fd`] - CAN FD frames and socket helpers
+//! * [`error_frame`] - decoding of CAN error frames (bus-off, error-passive, ...)
+//! * [`canfdtest`] - echo test between a host (generator) and a DUT
+//! * [`cyclic`] - cyclic transmission with drift-free timing
+//! * [`bcm`] - cyclic transmission by the kernel (broadcast manager)
+//! * [`transport`] - CAN bus access for the echo test (SocketCAN or in-memory)
+//! * [`shutdown`] - graceful shutdown on SIGINT and SIGTERM
+//!
+//! # Examples
+//! ```
+//! use can_utils::frame;
+//!
+//! let frame = frame::parse_frame_string("123#cafe").unwrap().classic().unwrap();
+//! assert_eq!(0x123, frame.id());
+//! assert_eq!("123#CAFE", frame::frame_to_string(&frame));
+//! ```
+
+pub mod bcm;
+pub mod canfdtest;
+pub mod cyclic;
+pub mod error_frame;
+pub mod fd;
+pub mod frame;
+pub mod id;
+pub mod shutdown;
+pub mod transport;
@@ -0,0 +1,65 @@
+//! Graceful shutdown on SIGINT and SIGTERM
+//!
+//! Long running tools like `canfdtest` stop cleanly on Ctrl-C: the signal
+//! handler only sets a flag, which is polled by the running test.
+
+use std::io;
+use std::mem;
+use std::ptr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, OnceLock};
+
+static STOP_FLAG: OnceLock<Arc<AtomicBool>> = OnceLock::new();
+
+/// Stores the flag set on termination, fails if a flag is already stored
+fn register_flag(stop_flag: &OnceLock<Arc<AtomicBool>>, flag: Arc<AtomicBool>) -> io::Result<()> {
+    stop_flag.set(flag)
+        .map_err(|_| io::Error::new(io::ErrorKind::AlreadyExists, "termination handler already installed"))
+}
+
+/// Sets the stored flag, if any (only async-signal-safe operations)
+fn request_stop(stop_flag: &OnceLock<Arc<AtomicBool>>) {
+    if let Some(flag) = stop_flag.get() {
+        flag.store(true, Ordering::SeqCst);
+    }
+}
+
+extern "C" fn handle_termination(_signal: libc::c_int) {
+    request_stop(&STOP_FLAG);
+}
+
+/// Sets the given flag on SIGINT or SIGTERM instead of terminating the process
+///
+/// Blocking reads are interrupted by the signals (`io::ErrorKind::Interrupted`),
+/// so the flag is noticed without waiting for the next frame. Can only be
+/// called once per process.
+/// # Arguments
+/// * 'flag' - flag set on termination, e.g. from `Host::stop_flag`
+pub fn stop_on_termination(flag: Arc<AtomicBool>) -> io::Result<()> {
+    register_flag(&STOP_FLAG, flag)?;
+    let mut action: libc::sigaction = unsafe { mem::zeroed() };
+    action.sa_sigaction = handle_termination as extern "C" fn(libc::c_int) as libc::sighandler_t;
+    // no SA_RESTART, so blocking reads return on the signal
+    action.sa_flags = 0;
+    unsafe { libc::sigemptyset(&mut action.sa_mask) };
+    for signal in [libc::SIGINT, libc::SIGTERM].iter() {
+        if unsafe { libc::sigaction(*signal, &action, ptr::null_mut()) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn test_stop_on_termination() {
+    // own flag storage, so no process wide signal handler is installed by the test
+    let stop_flag: OnceLock<Arc<AtomicBool>> = OnceLock::new();
+    request_stop(&stop_flag);
+    let flag: Arc<AtomicBool> = Arc::new(AtomicBool::new(false));
+    register_flag(&stop_flag, flag.clone())
+        .unwrap();
+    assert!(!flag.load(Ordering::SeqCst));
+    request_stop(&stop_flag);
+    assert!(flag.load(Ordering::SeqCst));
+    assert_eq!(io::ErrorKind::AlreadyExists, register_flag(&stop_flag, flag).unwrap_err().kind());
+}
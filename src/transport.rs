@@ -0,0 +1,286 @@
+//! Transport of CAN frames between the nodes of an echo test
+//!
+//! [`CanTransport`] is implemented for `socketcan::CANSocket` and for the
+//! in-memory [`LoopbackTransport`], which allows to run host and DUT without
+//! a (virtual) CAN interface, e.g. inside `cargo test`.
+
+use crate::fd;
+use crate::frame::AnyFrame;
+#[cfg(test)]
+use crate::fd::CanFdFrame;
+use socketcan::{CANSocket, ERR_MASK};
+#[cfg(test)]
+use socketcan::CANFrame;
+use std::cell::Cell;
+use std::io;
+use std::mem::size_of;
+use std::os::unix::io::AsRawFd;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Read and write access to a CAN bus
+pub trait CanTransport {
+    /// Blocking read of a single classic CAN or CAN FD frame
+    ///
+    /// Fails with `io::ErrorKind::WouldBlock` if a read timeout is set and
+    /// no frame was received in time.
+    fn read_frame(&self) -> io::Result<AnyFrame>;
+
+    /// Blocking write of a single frame, retrying until it gets sent
+    ///
+    /// CAN FD frames have to be enabled with [`CanTransport::set_fd_frames`].
+    fn write_frame(&self, frame: &AnyFrame) -> io::Result<()>;
+
+    /// Sets the timeout of [`CanTransport::read_frame`], `None` blocks forever
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()>;
+
+    /// Enables (or disables) receiving the frames written by the transport itself
+    fn set_recv_own_msgs(&self, enable: bool) -> io::Result<()>;
+
+    /// Enables (or disables) reading and writing CAN FD frames
+    fn set_fd_frames(&self, enable: bool) -> io::Result<()>;
+
+    /// Sets the error classes received as error frames, `0` receives none
+    fn set_error_filter(&self, mask: u32) -> io::Result<()>;
+}
+
+impl CanTransport for CANSocket {
+    fn read_frame(&self) -> io::Result<AnyFrame> {
+        fd::read_any_frame(self)
+    }
+
+    fn write_frame(&self, frame: &AnyFrame) -> io::Result<()> {
+        match frame {
+            AnyFrame::Classic(frame) => self.write_frame_insist(frame),
+            AnyFrame::Fd(frame) => fd::write_fd_frame_insist(self, frame),
+        }
+    }
+
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        // a zero timeout disables the timeout of the socket
+        CANSocket::set_read_timeout(self, timeout.unwrap_or_default())
+    }
+
+    fn set_recv_own_msgs(&self, enable: bool) -> io::Result<()> {
+        let value: libc::c_int = enable as libc::c_int;
+        let rv = unsafe {
+            libc::setsockopt(self.as_raw_fd(),
+                             libc::SOL_CAN_RAW,
+                             libc::CAN_RAW_RECV_OWN_MSGS,
+                             &value as *const libc::c_int as *const libc::c_void,
+                             size_of::<libc::c_int>() as libc::socklen_t)
+        };
+        if rv != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    fn set_fd_frames(&self, enable: bool) -> io::Result<()> {
+        fd::set_fd_frames(self, enable)
+    }
+
+    fn set_error_filter(&self, mask: u32) -> io::Result<()> {
+        CANSocket::set_error_filter(self, mask)
+    }
+}
+
+/// Connection of a [`LoopbackTransport`] to the bus
+struct Node {
+    sender: Sender<AnyFrame>,
+    fd_frames: Arc<AtomicBool>,
+    error_filter: Arc<AtomicU32>,
+}
+
+type Nodes = Arc<Mutex<Vec<Node>>>;
+
+/// In-memory CAN bus connecting [`LoopbackTransport`]s
+///
+/// A frame written by one transport is received by all other transports
+/// connected to the same bus (and by the transport itself if enabled with
+/// [`CanTransport::set_recv_own_msgs`]). Like a CAN socket, CAN FD frames are
+/// only received by transports with CAN FD frames enabled. Error frames can
+/// be written to emulate errors reported by the driver, they are only
+/// received by transports with a matching error filter.
+///
+/// # Examples
+/// ```
+/// use can_utils::transport::{CanTransport, LoopbackBus};
+/// use socketcan::CANFrame;
+///
+/// let bus = LoopbackBus::new();
+/// let host = bus.connect();
+/// let dut = bus.connect();
+/// host.write_frame(&CANFrame::new(0x77, &[1, 2], false, false).unwrap().into()).unwrap();
+/// assert_eq!(0x77, dut.read_frame().unwrap().id());
+/// ```
+#[derive(Clone, Default)]
+pub struct LoopbackBus {
+    nodes: Nodes,
+}
+
+impl LoopbackBus {
+    /// Creates a bus without any connected transport
+    pub fn new() -> LoopbackBus {
+        LoopbackBus::default()
+    }
+
+    /// Connects a new transport to the bus
+    pub fn connect(&self) -> LoopbackTransport {
+        let (sender, receiver) = mpsc::channel();
+        let fd_frames: Arc<AtomicBool> = Arc::new(AtomicBool::new(false));
+        let error_filter: Arc<AtomicU32> = Arc::new(AtomicU32::new(0));
+        let mut nodes = self.nodes.lock().unwrap();
+        nodes.push(Node {
+            sender,
+            fd_frames: Arc::clone(&fd_frames),
+            error_filter: Arc::clone(&error_filter),
+        });
+        LoopbackTransport {
+            index: nodes.len() - 1,
+            nodes: Arc::clone(&self.nodes),
+            receiver,
+            read_timeout: Cell::new(None),
+            recv_own_msgs: Cell::new(false),
+            fd_frames,
+            error_filter,
+        }
+    }
+}
+
+/// Node of a [`LoopbackBus`]
+pub struct LoopbackTransport {
+    index: usize,
+    nodes: Nodes,
+    receiver: Receiver<AnyFrame>,
+    read_timeout: Cell<Option<Duration>>,
+    recv_own_msgs: Cell<bool>,
+    fd_frames: Arc<AtomicBool>,
+    error_filter: Arc<AtomicU32>,
+}
+
+impl CanTransport for LoopbackTransport {
+    fn read_frame(&self) -> io::Result<AnyFrame> {
+        match self.read_timeout.get() {
+            Some(timeout) => match self.receiver.recv_timeout(timeout) {
+                Ok(frame) => Ok(frame),
+                Err(RecvTimeoutError::Timeout) =>
+                    Err(io::Error::new(io::ErrorKind::WouldBlock, "read timed out")),
+                Err(RecvTimeoutError::Disconnected) =>
+                    Err(io::Error::new(io::ErrorKind::BrokenPipe, "bus disconnected")),
+            },
+            None => self.receiver.recv()
+                .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "bus disconnected")),
+        }
+    }
+
+    fn write_frame(&self, frame: &AnyFrame) -> io::Result<()> {
+        if frame.is_fd() && !self.fd_frames.load(Ordering::SeqCst) {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "CAN FD frames not enabled"));
+        }
+        let nodes = self.nodes.lock().unwrap();
+        for (index, node) in nodes.iter().enumerate() {
+            if index == self.index && !self.recv_own_msgs.get() {
+                continue;
+            }
+            if frame.is_fd() && !node.fd_frames.load(Ordering::SeqCst) {
+                continue;
+            }
+            if frame.can_id().is_error() && frame.id() & node.error_filter.load(Ordering::SeqCst) == 0 {
+                continue;
+            }
+            // nodes which are gone just do not receive the frame anymore
+            let _ = node.sender.send(*frame);
+        }
+        Ok(())
+    }
+
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        self.read_timeout.set(timeout);
+        Ok(())
+    }
+
+    fn set_recv_own_msgs(&self, enable: bool) -> io::Result<()> {
+        self.recv_own_msgs.set(enable);
+        Ok(())
+    }
+
+    fn set_fd_frames(&self, enable: bool) -> io::Result<()> {
+        self.fd_frames.store(enable, Ordering::SeqCst);
+        Ok(())
+    }
+
+    fn set_error_filter(&self, mask: u32) -> io::Result<()> {
+        self.error_filter.store(mask & ERR_MASK, Ordering::SeqCst);
+        Ok(())
+    }
+}
+
+#[test]
+fn test_loopback_not_received_by_sender() {
+    let bus: LoopbackBus = LoopbackBus::new();
+    let first: LoopbackTransport = bus.connect();
+    let second: LoopbackTransport = bus.connect();
+    let third: LoopbackTransport = bus.connect();
+    let frame: CANFrame = CANFrame::new(0x123, &[1, 2, 3], false, false)
+        .unwrap();
+    first.write_frame(&frame.into()).unwrap();
+    assert_eq!(&[1, 2, 3], second.read_frame().unwrap().data());
+    assert_eq!(&[1, 2, 3], third.read_frame().unwrap().data());
+    first.set_read_timeout(Some(Duration::from_millis(10))).unwrap();
+    assert_eq!(io::ErrorKind::WouldBlock, first.read_frame().unwrap_err().kind());
+}
+
+#[test]
+fn test_loopback_recv_own_msgs() {
+    let bus: LoopbackBus = LoopbackBus::new();
+    let first: LoopbackTransport = bus.connect();
+    let second: LoopbackTransport = bus.connect();
+    first.set_recv_own_msgs(true).unwrap();
+    let frame: CANFrame = CANFrame::new(0x123, &[1, 2, 3], false, false)
+        .unwrap();
+    first.write_frame(&frame.into()).unwrap();
+    assert_eq!(0x123, first.read_frame().unwrap().id());
+    assert_eq!(0x123, second.read_frame().unwrap().id());
+}
+
+#[test]
+fn test_loopback_fd_frames() {
+    let bus: LoopbackBus = LoopbackBus::new();
+    let first: LoopbackTransport = bus.connect();
+    let second: LoopbackTransport = bus.connect();
+    let third: LoopbackTransport = bus.connect();
+    let frame: AnyFrame = CanFdFrame::new(0x123, &[0xaa; 12], 0)
+        .unwrap()
+        .into();
+    assert!(first.write_frame(&frame).is_err());
+    first.set_fd_frames(true).unwrap();
+    second.set_fd_frames(true).unwrap();
+    first.write_frame(&frame).unwrap();
+    assert!(second.read_frame().unwrap().is_fd());
+    // transports without CAN FD frames enabled do not receive them
+    third.set_read_timeout(Some(Duration::from_millis(10))).unwrap();
+    assert!(third.read_frame().is_err());
+}
+
+#[test]
+fn test_loopback_error_filter() {
+    let bus: LoopbackBus = LoopbackBus::new();
+    let driver = bus.connect();
+    let node = bus.connect();
+    node.set_read_timeout(Some(Duration::from_millis(10)))
+        .unwrap();
+    // bus-off
+    let error_frame: CANFrame = CANFrame::new(0x40, &[], false, true)
+        .unwrap();
+    driver.write_frame(&error_frame.into())
+        .unwrap();
+    assert!(node.read_frame().is_err());
+    node.set_error_filter(0x40)
+        .unwrap();
+    driver.write_frame(&error_frame.into())
+        .unwrap();
+    assert!(node.read_frame().unwrap().can_id().is_error());
+}